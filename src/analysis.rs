@@ -0,0 +1,172 @@
+use std::fmt;
+
+use glam::*;
+
+use crate::drawing::*;
+use crate::export::*;
+use crate::plotter::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct AnalysisParameters {
+    /// Number of regions the page is split in to measure coverage, across and down.
+    pub regions : (usize, usize),
+    /// Millilitres of ink per metre drawn.
+    pub ink_per_metre : f32,
+}
+
+impl Default for AnalysisParameters {
+    fn default() -> AnalysisParameters {
+        // A fineliner holding about 1 ml draws some 500 m of line.
+        AnalysisParameters { regions: (4, 3), ink_per_metre: 0.002_f32 }
+    }
+}
+
+/// What a drawing costs to plot, lengths in millimetres.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct DrawingMetrics {
+    pub path_length : f32,
+    /// Travel between strokes in plot order, from and back to the origin.
+    pub pen_up_length : f32,
+    pub segment_count : usize,
+    pub pen_lifts : usize,
+}
+
+impl DrawingMetrics {
+    /// `scale` converts drawing units into millimetres.
+    ///
+    /// Everything is measured on the export of the drawing. Lengths and lifts are those of the plot simulation,
+    /// which do not depend on the speeds of the plotter, so the estimate printed with the export and the analysis agree.
+    pub fn measure(drawing: &Drawing, scale: f32) -> DrawingMetrics {
+        DrawingMetrics::measure_exported(&exported_drawing(drawing), scale)
+    }
+
+    fn measure_exported(exported: &Drawing, scale: f32) -> DrawingMetrics {
+        let simulation = PlotSimulation::from_exported(exported, scale, &PlotterParameters::default());
+        DrawingMetrics {
+            path_length: simulation.pen_down_length,
+            pen_up_length: simulation.pen_up_length,
+            segment_count: exported.iter().map(|stroke| stroke.segment_count()).sum(),
+            pen_lifts: simulation.pen_lifts,
+        }
+    }
+
+    pub const CSV_HEADER : &'static str = "path_length_mm,segment_count,pen_lifts";
+
+    pub fn csv_row(&self) -> String {
+        format!("{:.2},{},{}", self.path_length, self.segment_count, self.pen_lifts)
+    }
+}
+
+/// Metrics of a drawing along with where on the page its ink goes.
+#[derive(Clone, Debug)]
+pub struct DrawingAnalysis {
+    pub metrics : DrawingMetrics,
+    /// In millimetres, None for an empty drawing.
+    pub bounds : Option<Bounds>,
+    /// Region count across and down, see `coverage`.
+    pub regions : (usize, usize),
+    /// Millimetres drawn per square millimetre in each region of the page, row after row.
+    pub coverage : Vec<f32>,
+    /// In millilitres.
+    pub ink : f32,
+}
+
+impl DrawingAnalysis {
+    /// `scale` converts drawing units into millimetres, coverage being measured over `page` in drawing units.
+    pub fn new(drawing: &Drawing, scale: f32, page: &Bounds, parameters: &AnalysisParameters) -> DrawingAnalysis {
+        let (columns, rows) = (parameters.regions.0.max(1), parameters.regions.1.max(1));
+        let region_size = page.size() / Vec2::new(columns as f32, rows as f32);
+        let mut region_lengths = vec![0_f32; columns * rows];
+
+        // Long segments are cut in pieces so each region gets the part of the line inside it.
+        // Lines the export leaves out are not drawn twice.
+        let step = region_size.min_element() / 8_f32;
+        let exported = exported_drawing(drawing);
+        for stroke in exported.iter() {
+            for pair in stroke.flattened_points(8).windows(2) {
+                let length = pair[0].distance(pair[1]);
+                let pieces = (length / step).ceil().max(1_f32) as usize;
+                for piece in 0..pieces {
+                    let middle = pair[0].lerp(pair[1], (piece as f32 + 0.5_f32) / pieces as f32);
+                    if !page.contains(middle) {
+                        continue;
+                    }
+
+                    let region = ((middle - page.min) / region_size).floor();
+                    let column = (region.x as usize).min(columns - 1);
+                    let row = (region.y as usize).min(rows - 1);
+                    region_lengths[row * columns + column] += length / pieces as f32;
+                }
+            }
+        }
+
+        let region_area = region_size.x * region_size.y / (scale * scale);
+        let metrics = DrawingMetrics::measure_exported(&exported, scale);
+        DrawingAnalysis {
+            metrics,
+            bounds: drawing.bounds().map(|bounds| Bounds { min: bounds.min / scale, max: bounds.max / scale }),
+            regions: (columns, rows),
+            coverage: region_lengths.iter().map(|length| length / scale / region_area).collect(),
+            ink: metrics.path_length / 1000_f32 * parameters.ink_per_metre,
+        }
+    }
+
+    /// Bounds of a coverage region in the units of `page`.
+    pub fn region_bounds(&self, index: usize, page: &Bounds) -> Bounds {
+        let region_size = page.size() / Vec2::new(self.regions.0 as f32, self.regions.1 as f32);
+        let min = page.min + Vec2::new((index % self.regions.0) as f32, (index / self.regions.0) as f32) * region_size;
+        Bounds { min, max: min + region_size }
+    }
+
+}
+
+impl fmt::Display for DrawingAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Pen down : {:.0} mm", self.metrics.path_length)?;
+        writeln!(f, "Pen up : {:.0} mm", self.metrics.pen_up_length)?;
+        writeln!(f, "Pen lifts : {}", self.metrics.pen_lifts)?;
+        writeln!(f, "Segments : {}", self.metrics.segment_count)?;
+        match self.bounds {
+            Some(bounds) => writeln!(f, "Bounds : {:.1}, {:.1} to {:.1}, {:.1} mm", bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y)?,
+            None => writeln!(f, "Bounds : empty")?,
+        }
+        writeln!(f, "Ink : {:.3} ml", self.ink)?;
+        write!(f, "Coverage (mm/mm2) :")?;
+        for row in self.coverage.chunks(self.regions.0) {
+            write!(f, "\n   ")?;
+            for density in row {
+                write!(f, " {:.3}", density)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_describe_the_exported_drawing() {
+        let mut drawing = Drawing::new();
+        let attributes = StrokeAttributes::default();
+        drawing.extend(Stroke::from_polyline(&[Vec2::new(10_f32, 0_f32), Vec2::new(20_f32, 0_f32), Vec2::new(20_f32, 10_f32)], attributes.clone()));
+        // Drawn again, the export leaves it out.
+        drawing.extend(Stroke::from_polyline(&[Vec2::new(20_f32, 0_f32), Vec2::new(10_f32, 0_f32)], attributes));
+
+        let metrics = DrawingMetrics::measure(&drawing, 2_f32);
+        assert_eq!(metrics.segment_count, 2);
+        assert!((metrics.path_length - 10_f32).abs() < 1e-4_f32);
+        assert_eq!(metrics.pen_lifts, 1);
+
+        let page = Bounds { min: Vec2::ZERO, max: Vec2::new(40_f32, 40_f32) };
+        let analysis = DrawingAnalysis::new(&drawing, 2_f32, &page, &AnalysisParameters::default());
+        assert_eq!(analysis.metrics.segment_count, metrics.segment_count);
+        let drawn : f32 = (0..analysis.coverage.len()).map(|index| {
+            let region = analysis.region_bounds(index, &page);
+            analysis.coverage[index] * region.size().x * region.size().y / 4_f32
+        }).sum();
+        assert!((drawn - metrics.path_length).abs() < 1e-3_f32);
+    }
+}
@@ -1,246 +1,634 @@
 use ggez::{*, graphics::MeshBuilder};
 use glam::*;
 
-use svg::Document;
-use svg::node::element::Path;
-use svg::node::element::path::Data;
-
 use crate::utils::*;
+use crate::drawing::*;
 use crate::font::*;
-use crate::signature::*;
 use crate::grid::*;
-use std::collections::HashSet;
-
-#[derive(Copy, Clone)]
-pub struct ApplicationParameters {
-    pub display_grid : bool,
-    pub print_grid : bool,
-    pub animate_instructions : bool,
-    pub walk_parameters : RandomWalkParameters,
+use crate::simplify::*;
+use crate::analysis::*;
+use crate::error::Error;
+use crate::ui::*;
+use crate::config::*;
+use crate::history::*;
+use crate::plotter::*;
+use crate::sketch::*;
+use crate::composer::*;
+
+// What a left click does in the preview.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EditMode {
+    Reseed,
+    ToggleTiles,
+    PinStarts,
+    // Dragging a rectangle regenerates the walks going through the tiles inside.
+    RegenerateRegion,
+    // Dragging draws freehand strokes in the sketch layer.
+    Sketch,
 }
 
-pub struct Application {
-    grid: Grid,
-    scale: f32,
-    size: Vec2,
-    font : Font,
-
-    parameters : ApplicationParameters,
+impl EditMode {
+    pub fn next(self) -> EditMode {
+        match self {
+            EditMode::Reseed => EditMode::ToggleTiles,
+            EditMode::ToggleTiles => EditMode::PinStarts,
+            EditMode::PinStarts => EditMode::RegenerateRegion,
+            EditMode::RegenerateRegion => EditMode::Sketch,
+            EditMode::Sketch => EditMode::Reseed,
+        }
+    }
+}
 
-    instructions: Vec<Instruction>,
+pub struct Application {
+    composer : Composer,
+    panel : Panel,
+    // Grid parameters from before the slider being dragged, the drawing only follows once it is released.
+    drag_start : Option<GridParameters>,
+    edit_mode : EditMode,
+    // Where the region being selected was started, in drawing units.
+    selection_start : Option<Vec2>,
+    sketch : Sketch,
+    view : View,
+    // Last mouse position while panning with the right or middle button.
+    pan_anchor : Option<Vec2>,
+    show_ruler : bool,
+    show_travel : bool,
+    show_analysis : bool,
+    // Analysis of the export, None once the drawing or the export settings changed.
+    analysis : Option<DrawingAnalysis>,
+    // Last error met, drawn over the preview.
+    error_message : Option<String>,
+    config_watcher : Option<FileWatcher>,
+    font_watcher : Option<FileWatcher>,
+    history : History,
 
     is_mouse_down: bool,
     is_print_down: bool,
+    is_smoothing_down: bool,
+    is_simplification_down: bool,
+    is_hatching_down: bool,
+    is_generator_down: bool,
+    is_panel_down: bool,
+    is_undo_down: bool,
+    is_redo_down: bool,
+    is_ruler_down: bool,
+    is_travel_down: bool,
+    is_analysis_down: bool,
+    is_simulation_down: bool,
+    is_faster_down: bool,
+    is_slower_down: bool,
+    is_edit_mode_down: bool,
+    is_undo_sketch_down: bool,
+    is_clear_sketch_down: bool,
     
-    animation_frame: i32,
+    simulation: Option<PlotSimulation>,
+    simulation_time: f32,
 }
 
 impl Application {
-    pub fn new(grid: Grid, scale: f32, size: Vec2, font : Font, parameters : ApplicationParameters) -> Application{
+    pub fn new(composer: Composer) -> Application{
         Application {
-            grid,
+            composer,
             is_mouse_down: false,
             is_print_down: false,
-            instructions: Vec::new(),
-            scale,
-            font,
-            size,
-            parameters,
-            animation_frame: match parameters.animate_instructions { true => 0, false => -20},
+            is_smoothing_down: false,
+            is_simplification_down: false,
+            is_hatching_down: false,
+            is_generator_down: false,
+            is_panel_down: false,
+            is_undo_down: false,
+            is_redo_down: false,
+            is_ruler_down: false,
+            is_travel_down: false,
+            is_analysis_down: false,
+            is_simulation_down: false,
+            is_faster_down: false,
+            is_slower_down: false,
+            is_edit_mode_down: false,
+            is_undo_sketch_down: false,
+            is_clear_sketch_down: false,
+            panel: Panel::new(Vec2::new(10_f32, 10_f32), 220_f32),
+            drag_start: None,
+            edit_mode: EditMode::Reseed,
+            selection_start: None,
+            sketch: Sketch::default(),
+            view: View::new(),
+            pan_anchor: None,
+            show_ruler: false,
+            show_travel: false,
+            show_analysis: false,
+            analysis: None,
+            error_message: None,
+            config_watcher: None,
+            font_watcher: None,
+            history: History::new(50),
+            simulation: None,
+            simulation_time: 0_f32,
         }
     }
 
-    pub fn print_to_svg(&self) {
-        let mut drawn_points : HashSet<OrderedPair> = std::collections::HashSet::new();
-        let mut data = Data::new();
-
-        if self.parameters.print_grid {
-            let mut shapes_to_draw : Vec<usize> = (0..self.grid.tiles.len()).collect();
-            let mut next_shape_index = (shapes_to_draw.len() - 1) / 2;
-            let mut start_vertex = 0 as usize;
-            let mut delta = 0;
-            
-            data = data.move_to((self.grid.tiles[0].vertices[0] / self.scale).from());
-            let mut current_position = Vec2::default();
-            while !shapes_to_draw.is_empty() {
-                let shape_index = shapes_to_draw[next_shape_index];
-                shapes_to_draw.remove(next_shape_index);
-                let tile = &self.grid.tiles[shape_index];
-                let mut current_vertex = tile.vertices[start_vertex];
-                if current_vertex != current_position {
-                    data = data.move_to((current_vertex / self.scale).from());
-                }
+    // Errors do not stop the preview, the last one is shown until something succeeds.
+    fn show_error(&mut self, error: &Error) {
+        println!("{}", error);
+        self.error_message = Some(error.to_string());
+    }
+}
 
-                for vert_index in 1..(tile.vertices.len() + 1) {
-                    let next_vertex = tile.vertices[(vert_index + start_vertex) % tile.vertices.len()];
-                    let pair = OrderedPair::new(current_vertex, next_vertex);
-                    let next_point = (next_vertex / self.scale).from();
-                    if !drawn_points.contains(&pair) {
-                        drawn_points.insert(pair);
-                        data = data.line_to(next_point);
-                    }
-                    else {
-                        data = data.move_to(next_point);
-                    }
-                    
-                    current_vertex = next_vertex;
-                }
-                
-                if shapes_to_draw.is_empty() {
-                    break;
-                }
+impl Application
+{
+    fn fill_mesh_builder(drawing : &Drawing, line_width: f32, mesh_builder : &mut MeshBuilder) -> bool {
+        let mut has_filled_mesh_builder = false;
 
-                let mut found = false;
-                for other_index in 0..shapes_to_draw.len() {
-                    let other_tile = &self.grid.tiles[shapes_to_draw[other_index]];
-                    for other_vertex_index in 0..other_tile.vertices.len() {
-                        let other_vertex = other_tile.vertices[other_vertex_index];
-                        if other_vertex == current_vertex {
-                            start_vertex = other_vertex_index;
-                            next_shape_index = other_index;
-                            found = true;
-                            break;
-                        }
-                    }
-                    
-                    if found {
-                        break;
-                    }
-                }
-                
-                if !found {
-                    next_shape_index = (shapes_to_draw.len() - 1) / 2;
-                    start_vertex = delta % self.grid.tiles[shapes_to_draw[next_shape_index]].vertices.len();
-                    delta = delta + 1;
-                }
-                
-                current_position = current_vertex;
+        for stroke in drawing {
+            let vertices = stroke.flattened_points(8);
+            if vertices.len() > 1 {
+                let pts = vertices.iter().map(|p| mint::Point2{x: p.x, y: p.y}).collect::<Vec<mint::Point2<f32>>>();
+                mesh_builder.line(&pts, line_width, graphics::Color::BLACK).unwrap();
+                has_filled_mesh_builder = true;
             }
         }
 
-        let mut current_position = Vec2::new(0_f32, 0_f32);
-        for instruction in &self.instructions {
-            match instruction {
-                Instruction::MoveTo(pos) => {
-                    data = data.move_to((*pos / self.scale).from());
-                    current_position = *pos;
-                },
-                Instruction::LineTo(pos) => {
-                    
-                    let pair = OrderedPair::new(current_position, *pos);
-                    if !drawn_points.contains(&pair) {
-                        drawn_points.insert(pair);
-                        data = data.line_to((*pos / self.scale).from());
-                    }
-
-                    current_position = *pos;
+        has_filled_mesh_builder
+    }
+
+    // What the plotter has drawn `time` seconds into the plot, travel moves dashed and the pen as a small circle.
+    fn fill_simulation_mesh_builder(simulation : &PlotSimulation, time: f32, line_width: f32, mesh_builder : &mut MeshBuilder) -> GameResult {
+        let started = simulation.started_segments(time);
+        let mut polyline : Vec<mint::Point2<f32>> = Vec::new();
+        let mut travel = Vec::new();
+        let mut pen_position = Vec2::ZERO;
+        for segment in &simulation.segments[..started] {
+            let end = match segment.end() > time {
+                true => segment.position_at(time),
+                false => segment.to,
+            };
+
+            pen_position = end;
+            if segment.pen_down {
+                if polyline.is_empty() {
+                    polyline.push(mint::Point2 { x: segment.from.x, y: segment.from.y });
                 }
+
+                polyline.push(mint::Point2 { x: end.x, y: end.y });
+                continue;
+            }
+
+            if polyline.len() > 1 {
+                mesh_builder.line(&polyline, line_width, graphics::Color::BLACK)?;
             }
+
+            polyline.clear();
+            travel.push((segment.from, end));
+        }
+
+        if polyline.len() > 1 {
+            mesh_builder.line(&polyline, line_width, graphics::Color::BLACK)?;
         }
 
-        let path = Path::new()
-            .set("fill", "none")
-            .set("stroke", "black")
-            .set("stroke-width", 0.4)
-            .set("d", data);
+        let travel : Vec<(Vec2, Vec2)> = travel.into_iter().filter(|(from, to)| from.distance_squared(*to) > f32::EPSILON).collect();
+        fill_dashed_segments(mesh_builder, &travel, line_width * 2_f32, line_width / 2_f32, graphics::Color::RED)?;
+        mesh_builder.circle(graphics::DrawMode::stroke(line_width / 2_f32), mint::Point2 { x: pen_position.x, y: pen_position.y }, line_width * 3_f32, 0.1_f32, graphics::Color::RED)?;
+        Ok(())
+    }
+}
 
-        let document = Document::new()
-            .set("viewBox", (0, 0, self.size.x, self.size.y))
-            .set("width", format!("{}mm",self.size.x))
-            .set("height", format!("{}mm",self.size.y))
-            .set("units", "mm")
-            .add(path);
+impl Application {
+    fn start_simulation(&mut self) {
+        let simulation = PlotSimulation::new(&self.composer.export_drawing(), self.composer.scale, &self.composer.parameters.plotter);
+        println!("Simulating the plot : {} at x{}", format_duration(simulation.duration), self.composer.parameters.playback_speed);
+        self.simulation = Some(simulation);
+        self.simulation_time = 0_f32;
+    }
 
-        let export_name = format!("Exports/AMG_{}.svg",get_signature_counter());
+    // Current values of everything the panel can edit.
+    fn controls(&self) -> Vec<Control> {
+        let mut controls = vec![
+            Control::toggle("display_grid", self.composer.parameters.display_grid),
+            Control::toggle("print_grid", self.composer.parameters.print_grid),
+            Control::slider("col", self.composer.grid_parameters.col as f32, 1_f32, 30_f32, true),
+            Control::slider("row", self.composer.grid_parameters.row as f32, 1_f32, 30_f32, true),
+            Control::slider("tile_scale", self.composer.grid_parameters.tile_scale, 4_f32, 40_f32, false),
+            Control::toggle("hexagon_grid", self.composer.grid_parameters.shape == GridShape::Hexagon),
+            Control::toggle("square_grid", self.composer.grid_parameters.shape == GridShape::Square),
+            Control::slider("subdivision_levels", self.composer.grid_parameters.subdivision_levels as f32, 0_f32, 4_f32, true),
+            Control::slider("subdivision_scale", self.composer.grid_parameters.subdivision_scale, 0.01_f32, 1_f32, false),
+            Control::slider("hatch_target", self.composer.parameters.hatching.target.map_or(-1_f32, |target| target.index() as f32), -1_f32, 2_f32, true),
+            Control::slider("hatch_pattern", self.composer.parameters.hatching.pattern.index() as f32, 0_f32, 3_f32, true),
+            Control::slider("hatch_spacing", self.composer.parameters.hatching.spacing, 0.2_f32, 5_f32, false),
+            Control::slider("hatch_angle", self.composer.parameters.hatching.angle, 0_f32, 180_f32, false),
+            Control::slider("hatch_probability", self.composer.parameters.hatching.probability, 0_f32, 1_f32, false),
+        ];
+
+        controls.extend(self.composer.generator.parameters().into_iter().map(Control::from));
+        controls
+    }
 
-        match svg::save(export_name, &document) {
-            Ok(_) => {
-                match increment_signature_counter() {
-                    Err(e) => panic!("{}", e),
-                    _=>()
-                }
-            }
-            
-            Err(e)=> panic!("{}", e),
+    // Sets a setting of the composer by name, returns false when nothing has that name.
+    // The grid is not rebuilt and nothing is regenerated.
+    fn set_setting(&mut self, name: &str, value: f32) -> bool {
+        self.analysis = None;
+        self.composer.set_setting(name, value)
+    }
+
+    fn apply_control(&mut self, control: &Control) {
+        let grid_parameters = self.composer.grid_parameters;
+        self.set_setting(&control.name, control.number());
+        if self.composer.grid_parameters != grid_parameters {
+            self.composer.rebuild_grid();
+        }
+
+        if !matches!(control.name.as_str(), "display_grid" | "print_grid") {
+            self.regenerate();
         }
     }
-}
 
-impl Application
-{
-    pub fn fill_mesh_builder(instructions : &Vec<Instruction>, max_segment_points: i32, mesh_builder : &mut MeshBuilder) -> bool {
-        let line_width = 2_f32;
-        let mut vertices = Vec::new();
-        let mut has_filled_mesh_builder = false;
+    pub fn watch_config(&mut self, file_name: &str) {
+        self.config_watcher = Some(FileWatcher::new(file_name));
+    }
 
-        for instruction in instructions {
-            match instruction {
-                Instruction::LineTo(pos) => {
-                    if max_segment_points < 0 || vertices.len() < max_segment_points as usize {
-                        vertices.push(pos.to_owned());
-                    }
-                },
-                Instruction::MoveTo(pos) => {
-                    if vertices.len() > 1 {
-                        let pts = vertices.to_owned().into_iter().map(|p| mint::Point2{x: p.x, y: p.y}).collect::<Vec<mint::Point2<f32>>>();
-                        mesh_builder.line(&pts, line_width, graphics::Color::BLACK).unwrap();
-                        has_filled_mesh_builder = true;
-                    }
-
-                    vertices.clear();
-                    vertices.push(pos.to_owned());
+    pub fn watch_font(&mut self, file_name: &str) {
+        self.font_watcher = Some(FileWatcher::new(file_name));
+    }
+
+    // Reloads the watched files that changed on disk and regenerates with the same seed.
+    fn reload_changed_files(&mut self) {
+        let mut changed = false;
+        if let Some(watcher) = &mut self.config_watcher {
+            if watcher.has_changed() {
+                match Config::load(&watcher.file_name) {
+                    Ok(config) => {
+                        println!("Reloading {}", watcher.file_name);
+                        self.composer.apply_config(&config);
+                        changed = true;
+                    },
+                    Err(error) => self.show_error(&error),
                 }
             }
         }
-        
-        if vertices.len() > 1 {
-            let pts = vertices.to_owned().into_iter().map(|p| mint::Point2{x: p.x, y: p.y}).collect::<Vec<mint::Point2<f32>>>();
-            mesh_builder.line(&pts, line_width, graphics::Color::BLACK).unwrap();
-            has_filled_mesh_builder = true;
+
+        if let Some(watcher) = &mut self.font_watcher {
+            if watcher.has_changed() {
+                match Font::load(&watcher.file_name) {
+                    Ok(font) => {
+                        println!("Reloading {}", watcher.file_name);
+                        self.composer.font = font;
+                        changed = true;
+                    },
+                    Err(error) => self.show_error(&error),
+                }
+            }
+        }
+
+        if changed {
+            self.error_message = None;
+            self.regenerate();
         }
+    }
+
+    pub fn regenerate(&mut self) {
+        println!("Generator : {} seed {}", self.composer.generator.name(), self.composer.seed);
+        self.regenerate_drawing(None);
+    }
 
-        return has_filled_mesh_builder;
+    // The history entry keeps the seed of the whole drawing, the region having been walked from another one.
+    fn regenerate_region(&mut self, region: &[usize]) {
+        self.regenerate_drawing(Some(region));
     }
 
-    pub fn random_walk_into_instrution(&mut self) {
-        self.grid.random_walk(self.parameters.walk_parameters, &mut self.instructions);
+    fn regenerate_drawing(&mut self, region: Option<&[usize]>) {
+        if let Err(error) = self.composer.generate(region) {
+            self.show_error(&error);
+        }
+
+        self.record_history();
     }
 
-    pub fn sign_into_instructions(&mut self) {
-        let signature = get_signature();
-        let signature_height = 9.0_f32;
-        let signature_width = self.font.get_width(signature, signature_height);
-        let signature_margine = 15_f32;
-        self.font.print_in_instructions(get_signature(), Vec2::new(self.size.x * self.scale - signature_width - signature_margine, self.size.y * self.scale - 3_f32), signature_height, &mut self.instructions);
+    // Swaps the sketched strokes of the drawing for the current ones, the generated content and its seed staying as they are.
+    fn recompose_sketch(&mut self) {
+        self.composer.drawing.retain(|stroke| stroke.attributes.layer != "sketch");
+        self.composer.drawing.append(self.composer.sketch.clone());
+        self.record_history();
+    }
+
+    fn record_history(&mut self) {
+        let entry = HistoryEntry {
+            drawing: self.composer.drawing.clone(),
+            sketch: self.composer.sketch.clone(),
+            seed: self.composer.seed,
+            generator: self.composer.generator.name(),
+            parameters: self.composer.generator.parameters(),
+            grid_parameters: self.composer.grid_parameters,
+            generator_cache: self.composer.generator.cache(),
+        };
+
+        self.history.push(entry);
+        self.simulation = None;
+        self.analysis = None;
+        if self.composer.parameters.animate_instructions {
+            self.start_simulation();
+        }
+    }
+
+    fn undo(&mut self) -> bool {
+        match self.history.undo().cloned() {
+            Some(entry) => {
+                self.show_history_entry(entry);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn redo(&mut self) -> bool {
+        match self.history.redo().cloned() {
+            Some(entry) => {
+                self.show_history_entry(entry);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Displays a history entry and restores its seed and settings, so it can be tweaked further.
+    fn show_history_entry(&mut self, entry: HistoryEntry) {
+        if entry.generator != self.composer.generator.name() {
+            self.composer.set_generator(entry.generator);
+        }
+
+        for parameter in &entry.parameters {
+            self.composer.generator.set_parameter(parameter.name, parameter.value);
+        }
+
+        self.composer.generator.restore_cache(entry.generator_cache);
+
+        if entry.grid_parameters != self.composer.grid_parameters {
+            self.composer.grid_parameters = entry.grid_parameters;
+            self.composer.rebuild_grid();
+        }
+
+        let (position, count) = self.history.position();
+        println!("History {} / {} : {} seed {}", position, count, entry.generator, entry.seed);
+        self.composer.seed = entry.seed;
+        self.composer.drawing = entry.drawing;
+        self.composer.sketch = entry.sketch;
+        self.simulation = None;
+        self.analysis = None;
     }
 }
 
 impl ggez::event::EventHandler<GameError> for Application {
     fn update(&mut self, ctx: &mut Context) -> Result<(), GameError> {
-        
-        let mouse_position = input::mouse::position(ctx);
-        let mouse_position = Vec2::new(mouse_position.x, mouse_position.y);
-        
+        // Checking file dates every frame is wasteful, twice a second is responsive enough.
+        if timer::ticks(ctx) % 30 == 0 {
+            self.reload_changed_files();
+        }
+
         let was_pressed = self.is_mouse_down;
         self.is_mouse_down = input::mouse::button_pressed(ctx, event::MouseButton::Left);
-        if was_pressed != self.is_mouse_down {
-            if self.is_mouse_down {
-                self.instructions.clear();
-                self.animation_frame = match self.parameters.animate_instructions { true => 0, false => -20};
-                self.random_walk_into_instrution();
-                self.sign_into_instructions();
+
+        let was_panel_down = self.is_panel_down;
+        self.is_panel_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Tab);
+        if was_panel_down != self.is_panel_down && self.is_panel_down {
+            self.panel.visible = !self.panel.visible;
+        }
+
+        // Clicks landing on the panel edit parameters instead of drawing a new seed.
+        let mouse = input::mouse::position(ctx);
+        let mouse = Vec2::new(mouse.x, mouse.y);
+        let mut controls = self.controls();
+        let on_panel = self.panel.is_dragging() || self.panel.contains(controls.len(), mouse);
+        if let Some(index) = self.panel.update(&mut controls, mouse, self.is_mouse_down, was_pressed) {
+            match self.panel.is_dragging() {
+                true => {
+                    self.drag_start.get_or_insert(self.composer.grid_parameters);
+                    self.set_setting(&controls[index].name, controls[index].number());
+                },
+                false => self.apply_control(&controls[index]),
+            }
+        }
+
+        // A dragged slider only rebuilds and regenerates once, when it is released.
+        if !self.panel.is_dragging() {
+            if let Some(grid_parameters) = self.drag_start.take() {
+                if self.composer.grid_parameters != grid_parameters {
+                    self.composer.rebuild_grid();
+                }
+
+                self.regenerate();
+            }
+        }
+
+        let is_clicked = was_pressed != self.is_mouse_down && self.is_mouse_down && !on_panel;
+        let was_smoothing_down = self.is_smoothing_down;
+        self.is_smoothing_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::M);
+        if was_smoothing_down != self.is_smoothing_down && self.is_smoothing_down {
+            if let Some(index) = self.composer.generator.parameter("smoothing_mode") {
+                let smoothing_mode = SmoothingMode::from_index(index as usize).next();
+                self.composer.generator.set_parameter("smoothing_mode", smoothing_mode.index() as f32);
+                println!("Smoothing mode : {:?}", smoothing_mode);
             }
         }
 
+        let was_simplification_down = self.is_simplification_down;
+        self.is_simplification_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::V);
+        if was_simplification_down != self.is_simplification_down && self.is_simplification_down {
+            self.composer.parameters.simplification = match self.composer.parameters.simplification {
+                None => Some(SimplificationParameters { method: SimplificationMethod::RamerDouglasPeucker, tolerance: SimplificationParameters::DEFAULT_TOLERANCE }),
+                Some(SimplificationParameters { method: SimplificationMethod::RamerDouglasPeucker, tolerance }) => Some(SimplificationParameters { method: SimplificationMethod::VisvalingamWhyatt, tolerance }),
+                Some(_) => None,
+            };
+
+            self.analysis = None;
+            match self.composer.parameters.simplification {
+                Some(_) => { self.composer.simplified_drawing(); },
+                None => println!("Simplification disabled"),
+            }
+        }
+
+        let was_hatching_down = self.is_hatching_down;
+        self.is_hatching_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::H);
+        if was_hatching_down != self.is_hatching_down && self.is_hatching_down {
+            let hatching = &mut self.composer.parameters.hatching;
+            if hatching.target.is_some() {
+                hatching.pattern = hatching.pattern.next();
+                println!("Hatching pattern : {:?}", hatching.pattern);
+            }
+        }
+
+        let was_generator_down = self.is_generator_down;
+        self.is_generator_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::F);
+        if was_generator_down != self.is_generator_down && self.is_generator_down {
+            let name = self.composer.registry.next_name(self.composer.generator.name());
+            self.composer.set_generator(name);
+        }
+
+        let was_edit_mode_down = self.is_edit_mode_down;
+        self.is_edit_mode_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::E);
+        if was_edit_mode_down != self.is_edit_mode_down && self.is_edit_mode_down {
+            self.edit_mode = self.edit_mode.next();
+            self.selection_start = None;
+            println!("Edit mode : {:?}", self.edit_mode);
+        }
+
+        // In reseed mode a click draws a new seed, tile edits and the other keys keep the seed under new settings.
+        let mut needs_regeneration = (was_generator_down != self.is_generator_down && self.is_generator_down) || (was_smoothing_down != self.is_smoothing_down && self.is_smoothing_down) || (was_hatching_down != self.is_hatching_down && self.is_hatching_down);
+        let world = self.view.world_position(mouse);
+        if is_clicked {
+            match self.edit_mode {
+                EditMode::Reseed => {
+                    self.composer.seed = rand::random();
+                    needs_regeneration = true;
+                },
+                EditMode::ToggleTiles => if let Some(index) = self.composer.grid.tile_at(world) {
+                    self.composer.tile_constraints.toggle_excluded(index);
+                    needs_regeneration = true;
+                },
+                EditMode::PinStarts => if let Some(index) = self.composer.grid.tile_at(world) {
+                    self.composer.tile_constraints.toggle_start(index);
+                    needs_regeneration = true;
+                },
+                EditMode::RegenerateRegion => self.selection_start = Some(world),
+                EditMode::Sketch => self.sketch.add_point(world, 0_f32),
+            }
+        }
+
+        if self.sketch.is_drawing() {
+            match self.is_mouse_down {
+                true => self.sketch.add_point(world, 1.5_f32 / self.view.zoom),
+                false => if let Some(stroke) = self.sketch.finish_stroke(&self.composer.parameters.sketch, self.composer.scale) {
+                    self.composer.sketch.add(stroke.clone());
+                    self.composer.drawing.add(stroke);
+                    self.record_history();
+                },
+            }
+        }
+
+        // Backspace removes the last sketched stroke, delete removes them all.
+        let was_undo_sketch_down = self.is_undo_sketch_down;
+        self.is_undo_sketch_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Back);
+        if was_undo_sketch_down != self.is_undo_sketch_down && self.is_undo_sketch_down && self.composer.sketch.strokes.pop().is_some() {
+            self.recompose_sketch();
+        }
+
+        let was_clear_sketch_down = self.is_clear_sketch_down;
+        self.is_clear_sketch_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Delete);
+        if was_clear_sketch_down != self.is_clear_sketch_down && self.is_clear_sketch_down && !self.composer.sketch.is_empty() {
+            self.composer.sketch.clear();
+            self.recompose_sketch();
+        }
+
+        if !self.is_mouse_down {
+            if let Some(start) = self.selection_start.take() {
+                let selection = Bounds::from_points(&[start, world]);
+                let region : Vec<usize> = self.composer.grid.tiles.iter().filter(|tile| selection.contains(tile.position)).map(|tile| tile.index).collect();
+                if !region.is_empty() {
+                    self.regenerate_region(&region);
+                }
+            }
+        }
+
+        if needs_regeneration {
+            self.regenerate();
+        }
+
+        // Left and right arrows go back and forth in the history of generated drawings.
+        let was_undo_down = self.is_undo_down;
+        self.is_undo_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Left);
+        if was_undo_down != self.is_undo_down && self.is_undo_down {
+            self.undo();
+        }
+
+        let was_redo_down = self.is_redo_down;
+        self.is_redo_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Right);
+        if was_redo_down != self.is_redo_down && self.is_redo_down {
+            self.redo();
+        }
+
+        // Dragging with the right or middle button pans the view, R resets it.
+        if input::mouse::button_pressed(ctx, event::MouseButton::Right) || input::mouse::button_pressed(ctx, event::MouseButton::Middle) {
+            if let Some(anchor) = self.pan_anchor {
+                self.view.offset += mouse - anchor;
+            }
+
+            self.pan_anchor = Some(mouse);
+        }
+        else {
+            self.pan_anchor = None;
+        }
+
+        if input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::R) {
+            self.view = View::new();
+        }
+
+        let was_ruler_down = self.is_ruler_down;
+        self.is_ruler_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::G);
+        if was_ruler_down != self.is_ruler_down && self.is_ruler_down {
+            self.show_ruler = !self.show_ruler;
+        }
+
+        let was_travel_down = self.is_travel_down;
+        self.is_travel_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::T);
+        if was_travel_down != self.is_travel_down && self.is_travel_down {
+            self.show_travel = !self.show_travel;
+        }
+
+        // I shows what plotting the export would take, with the coverage of each region of the page shaded.
+        let was_analysis_down = self.is_analysis_down;
+        self.is_analysis_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::I);
+        if was_analysis_down != self.is_analysis_down && self.is_analysis_down {
+            self.show_analysis = !self.show_analysis;
+        }
+
+        if self.show_analysis && self.analysis.is_none() {
+            self.analysis = Some(self.composer.analyze());
+        }
+
+        // A plays the plot simulation back or stops it, up and down change its speed and S skips to the end.
+        let was_simulation_down = self.is_simulation_down;
+        self.is_simulation_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::A);
+        if was_simulation_down != self.is_simulation_down && self.is_simulation_down {
+            match self.simulation {
+                Some(_) => self.simulation = None,
+                None => self.start_simulation(),
+            }
+        }
+
+        let was_faster_down = self.is_faster_down;
+        self.is_faster_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Up);
+        if was_faster_down != self.is_faster_down && self.is_faster_down {
+            self.composer.parameters.playback_speed *= 2_f32;
+            println!("Playback speed : x{}", self.composer.parameters.playback_speed);
+        }
+
+        let was_slower_down = self.is_slower_down;
+        self.is_slower_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::Down);
+        if was_slower_down != self.is_slower_down && self.is_slower_down {
+            self.composer.parameters.playback_speed = (self.composer.parameters.playback_speed / 2_f32).max(0.25_f32);
+            println!("Playback speed : x{}", self.composer.parameters.playback_speed);
+        }
+
         if input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::S) {
-            self.animation_frame = -20;
+            self.simulation = None;
+        }
+
+        if let Some(simulation) = &self.simulation {
+            self.simulation_time += timer::delta(ctx).as_secs_f32() * self.composer.parameters.playback_speed;
+            if self.simulation_time > simulation.duration {
+                self.simulation = None;
+            }
         }
 
         let was_down = self.is_print_down;
         self.is_print_down = input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::P);
         if was_down != self.is_print_down && self.is_print_down
         {
-            self.print_to_svg();
+            match self.composer.print_to_svg() {
+                Ok(export_name) => {
+                    println!("Exported {}", export_name);
+                    self.error_message = None;
+                },
+                Err(error) => self.show_error(&error),
+            }
         }
 
         Ok(())
@@ -249,29 +637,106 @@ impl ggez::event::EventHandler<GameError> for Application {
     fn draw(&mut self, ctx: &mut Context) -> Result<(), GameError> {
         graphics::clear(ctx, graphics::Color::WHITE);
 
-        if self.animation_frame > -1 {
-            self.animation_frame = self.animation_frame + 1;
+        // Everything but the panel is in drawing units, line widths are divided by the zoom to stay constant on screen.
+        let thin_line = 1_f32 / self.view.zoom;
+        let line_width = 2_f32 / self.view.zoom;
+        let page = self.composer.page_bounds();
+        let mb = &mut graphics::MeshBuilder::new();
+        if self.show_ruler {
+            fill_millimetre_grid(mb, &page, self.composer.scale, &self.view, thin_line)?;
         }
 
-        let mb = &mut graphics::MeshBuilder::new();
-        
-        let has_filled_mesh_builder = Application::fill_mesh_builder(&self.instructions, self.animation_frame / 4, mb);
+        fill_page_frame(mb, &page, &self.composer.safe_area(), thin_line)?;
+        match &self.simulation {
+            Some(simulation) => Application::fill_simulation_mesh_builder(simulation, self.simulation_time, line_width, mb)?,
+            None => { Application::fill_mesh_builder(&self.composer.drawing, line_width, mb); },
+        }
 
-        if self.parameters.display_grid
+        if let (true, Some(analysis)) = (self.show_analysis, &self.analysis) {
+            let max_coverage = analysis.coverage.iter().cloned().fold(f32::EPSILON, f32::max);
+            for (index, coverage) in analysis.coverage.iter().enumerate() {
+                let region = analysis.region_bounds(index, &page);
+                let size = region.size();
+                let color = graphics::Color::new(1_f32, 0.5_f32, 0_f32, 0.3_f32 * coverage / max_coverage);
+                mb.rectangle(graphics::DrawMode::fill(), graphics::Rect::new(region.min.x, region.min.y, size.x, size.y), color)?;
+            }
+        }
+
+        if self.show_travel {
+            fill_dashed_segments(mb, &self.composer.drawing.pen_up_moves(), 4_f32 / self.view.zoom, thin_line, graphics::Color::RED)?;
+        }
+
+        if self.composer.parameters.display_grid
         {
-            for tile in &self.grid.tiles {
-                mb.polygon(graphics::DrawMode::Stroke(graphics::StrokeOptions::default().with_line_width(2_f32)), &tile.vertices, graphics::Color::BLACK).unwrap();
+            for tile in &self.composer.grid.tiles {
+                mb.polygon(graphics::DrawMode::Stroke(graphics::StrokeOptions::default().with_line_width(line_width)), &tile.vertices, graphics::Color::BLACK).unwrap();
             }
         }
         
-        if has_filled_mesh_builder {
-            let mesh = mb.build(ctx)?;
-            match graphics::draw(ctx, &mesh, graphics::DrawParam::new()) {
-                Ok(_) => (),
-                Err(e) => println!("ERROR : {:#?}", e)
+        for index in &self.composer.tile_constraints.excluded {
+            if let Some(tile) = self.composer.grid.tiles.get(*index) {
+                mb.polygon(graphics::DrawMode::fill(), &tile.vertices, graphics::Color::new(0_f32, 0_f32, 0_f32, 0.15_f32))?;
+            }
+        }
+
+        for index in &self.composer.tile_constraints.starts {
+            if let Some(tile) = self.composer.grid.tiles.get(*index) {
+                mb.circle(graphics::DrawMode::stroke(line_width), tile.position, self.composer.grid.tile_scale / 3_f32, 0.1_f32, graphics::Color::BLUE)?;
             }
         }
 
+        if self.sketch.current().len() > 1 {
+            mb.line(self.sketch.current(), line_width, graphics::Color::BLUE)?;
+        }
+
+        if let Some(start) = self.selection_start {
+            let mouse = input::mouse::position(ctx);
+            let selection = Bounds::from_points(&[start, self.view.world_position(Vec2::new(mouse.x, mouse.y))]);
+            let size = selection.size();
+            if size.x > 0_f32 && size.y > 0_f32 {
+                mb.rectangle(graphics::DrawMode::stroke(thin_line), graphics::Rect::new(selection.min.x, selection.min.y, size.x, size.y), graphics::Color::BLUE)?;
+            }
+        }
+
+        let mesh = mb.build(ctx)?;
+        match graphics::draw(ctx, &mesh, self.view.draw_param()) {
+            Ok(_) => (),
+            Err(e) => println!("ERROR : {:#?}", e)
+        }
+
+        if self.show_ruler {
+            queue_ruler_labels(ctx, &page, self.composer.scale, &self.view);
+            graphics::draw_queued_text(ctx, graphics::DrawParam::new(), None, graphics::FilterMode::Linear)?;
+        }
+
+        if let (true, Some(analysis)) = (self.show_analysis, &self.analysis) {
+            let text = graphics::Text::new(graphics::TextFragment::new(analysis.to_string()).scale(12_f32));
+            let width = graphics::drawable_size(ctx).0;
+            graphics::queue_text(ctx, &text, mint::Point2 { x: width - text.width(ctx) - 10_f32, y: 10_f32 }, Some(graphics::Color::BLACK));
+            graphics::draw_queued_text(ctx, graphics::DrawParam::new(), None, graphics::FilterMode::Linear)?;
+        }
+
+        if let Some(message) = &self.error_message {
+            let text = graphics::Text::new(graphics::TextFragment::new(message.clone()).scale(14_f32));
+            let height = graphics::drawable_size(ctx).1;
+            graphics::queue_text(ctx, &text, mint::Point2 { x: 10_f32, y: height - 44_f32 }, Some(graphics::Color::RED));
+            graphics::draw_queued_text(ctx, graphics::DrawParam::new(), None, graphics::FilterMode::Linear)?;
+        }
+
+        if self.edit_mode != EditMode::Reseed {
+            let text = graphics::Text::new(graphics::TextFragment::new(format!("Edit mode : {:?}", self.edit_mode)).scale(14_f32));
+            let height = graphics::drawable_size(ctx).1;
+            graphics::queue_text(ctx, &text, mint::Point2 { x: 10_f32, y: height - 24_f32 }, Some(graphics::Color::BLUE));
+            graphics::draw_queued_text(ctx, graphics::DrawParam::new(), None, graphics::FilterMode::Linear)?;
+        }
+
+        self.panel.draw(ctx, &self.controls())?;
+
         graphics::present(ctx)
     }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, _x: f32, y: f32) {
+        let mouse = input::mouse::position(ctx);
+        self.view.zoom_at(Vec2::new(mouse.x, mouse.y), 1.1_f32.powf(y));
+    }
 }
\ No newline at end of file
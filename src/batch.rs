@@ -0,0 +1,247 @@
+use glam::*;
+
+use crate::config::*;
+use crate::drawing::*;
+use crate::error::*;
+use crate::font::*;
+
+/// One drawing of a batch, the settings being applied on top of the ones from before the batch, then generated with the seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variation {
+    pub seed : u64,
+    pub settings : Vec<(String, f32)>,
+}
+
+impl Variation {
+    /// Seed and settings, as written under the thumbnail of the contact sheet.
+    pub fn label(&self) -> String {
+        let mut label = format!("seed {}", self.seed);
+        for (name, value) in &self.settings {
+            label.push_str(&format!(" {}={}", name, format_value(*value)));
+        }
+
+        label
+    }
+
+    /// File name of the variation, without extension.
+    pub fn file_stem(&self) -> String {
+        let mut stem = format!("seed_{}", self.seed);
+        for (name, value) in &self.settings {
+            stem.push_str(&format!("_{}_{}", name, format_value(*value)));
+        }
+
+        stem
+    }
+}
+
+/// File names of the variations without extension, in the same order. Values closer than the three decimals
+/// of the names are written the same, so a number is added to a name already taken.
+pub fn file_stems(variations: &[Variation]) -> Vec<String> {
+    let mut stems : Vec<String> = Vec::new();
+    for variation in variations {
+        let stem = variation.file_stem();
+        let mut unique = stem.clone();
+        let mut count = 1;
+        while stems.contains(&unique) {
+            count += 1;
+            unique = format!("{}_{}", stem, count);
+        }
+
+        stems.push(unique);
+    }
+
+    stems
+}
+
+fn format_value(value: f32) -> String {
+    match value.fract() == 0_f32 {
+        true => format!("{}", value as i64),
+        false => format!("{:.3}", value).trim_end_matches('0').trim_end_matches('.').to_owned(),
+    }
+}
+
+/// `count` variations with consecutive seeds starting at `first_seed`.
+pub fn seed_variations(first_seed: u64, count: usize) -> Vec<Variation> {
+    (0..count as u64).map(|index| Variation { seed: first_seed.wrapping_add(index), settings: Vec::new() }).collect()
+}
+
+/// `count` variations with the same seed, `name` going from `from` to `to` in even steps.
+pub fn sweep_variations(seed: u64, name: &str, from: f32, to: f32, count: usize) -> Vec<Variation> {
+    (0..count).map(|index| {
+        let ratio = match count > 1 { true => index as f32 / (count - 1) as f32, false => 0_f32 };
+        Variation { seed, settings: vec![(name.to_owned(), from + (to - from) * ratio)] }
+    }).collect()
+}
+
+/// Values to try for each setting, every combination making a variation.
+///
+/// Written like a config file, one setting per line as a list `name = 1, 2, 4` or a range `name = from:to:step`,
+/// usually over `slice_percentage`, `smooth_number_of_points`, `smooth_sharpness`, `tile_scale`, `col` and `row`.
+#[derive(Clone, Debug, Default)]
+pub struct SweepSpec {
+    pub axes : Vec<(String, Vec<f32>)>,
+}
+
+impl SweepSpec {
+    pub fn parse(text: &str) -> std::result::Result<SweepSpec, String> {
+        let config = Config::parse(text)?;
+        let mut spec = SweepSpec::default();
+        for (name, value) in config.entries() {
+            let values = SweepSpec::parse_values(value).ok_or_else(|| format!("{} expects a list a, b, c or a range from:to:step, found \"{}\"", name, value))?;
+            spec.axes.retain(|(other, _)| other != name);
+            spec.axes.push((name.to_owned(), values));
+        }
+
+        Ok(spec)
+    }
+
+    pub fn load(file_name: &str) -> Result<SweepSpec> {
+        let text = std::fs::read_to_string(file_name).map_err(|error| Error::io(file_name, error))?;
+        SweepSpec::parse(&text).map_err(|message| Error::Config { file_name: file_name.to_owned(), message })
+    }
+
+    fn parse_values(value: &str) -> Option<Vec<f32>> {
+        let bounds : Vec<&str> = value.split(':').map(|bound| bound.trim()).collect();
+        match bounds[..] {
+            [from, to, step] => {
+                let (from, to, step) = (from.parse::<f32>().ok()?, to.parse::<f32>().ok()?, step.parse::<f32>().ok()?);
+                if step <= 0_f32 || to < from {
+                    return None;
+                }
+
+                // Counted rather than accumulated so the last value is not lost to rounding.
+                let count = ((to - from) / step + 1e-4_f32).floor() as usize;
+                Some((0..=count).map(|index| from + step * index as f32).collect())
+            },
+            [_] => value.split(',').map(|item| item.trim().parse::<f32>().ok()).collect(),
+            _ => None,
+        }
+    }
+
+    /// Number of combinations, the product of the number of values of every setting.
+    pub fn len(&self) -> usize {
+        self.axes.iter().map(|(_, values)| values.len()).product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every combination for each of `seed_count` consecutive seeds starting at `first_seed`, the last setting changing fastest.
+    pub fn variations(&self, first_seed: u64, seed_count: usize) -> Vec<Variation> {
+        let mut variations = seed_variations(first_seed, seed_count);
+        for (name, values) in &self.axes {
+            variations = variations.iter()
+                .flat_map(|variation| values.iter().map(move |value| {
+                    let mut variation = variation.clone();
+                    variation.settings.push((name.clone(), *value));
+                    variation
+                }))
+                .collect();
+        }
+
+        variations
+    }
+}
+
+/// Layout of the thumbnails of a batch on a single sheet, sizes in millimetres.
+#[derive(Copy, Clone, Debug)]
+pub struct ContactSheet {
+    /// 0 picks as many columns as rows.
+    pub columns : usize,
+    /// Size of a thumbnail relative to the page.
+    pub thumbnail_scale : f32,
+    pub margin : f32,
+    pub label_height : f32,
+}
+
+impl Default for ContactSheet {
+    fn default() -> ContactSheet {
+        ContactSheet { columns: 0, thumbnail_scale: 0.3_f32, margin: 5_f32, label_height: 2_f32 }
+    }
+}
+
+impl ContactSheet {
+    /// Tiles the drawings made on a page of `page_size` millimetres, each framed and labelled below.
+    ///
+    /// Returns the sheet and its size in millimetres, `scale` converting millimetres into drawing units.
+    pub fn layout(&self, thumbnails: &[(Drawing, String)], page_size: Vec2, scale: f32, font: &Font) -> (Drawing, Vec2) {
+        let columns = match self.columns {
+            0 => (thumbnails.len() as f32).sqrt().ceil().max(1_f32) as usize,
+            columns => columns,
+        };
+        let rows = thumbnails.len().div_ceil(columns).max(1);
+
+        let thumbnail_size = page_size * self.thumbnail_scale;
+        let cell_size = thumbnail_size + Vec2::new(self.margin, self.margin + self.label_height * 2_f32);
+        let size = Vec2::new(columns as f32, rows as f32) * cell_size + Vec2::splat(self.margin);
+
+        let mut sheet = Drawing::new();
+        for (index, (drawing, label)) in thumbnails.iter().enumerate() {
+            let origin = (Vec2::splat(self.margin) + Vec2::new((index % columns) as f32, (index / columns) as f32) * cell_size) * scale;
+            let mut thumbnail = drawing.clone();
+            thumbnail.scale(Vec2::splat(self.thumbnail_scale), Vec2::ZERO);
+            thumbnail.translate(origin);
+            sheet.append(thumbnail);
+
+            let corner = origin + thumbnail_size * scale;
+            let frame = [origin, Vec2::new(corner.x, origin.y), corner, Vec2::new(origin.x, corner.y), origin];
+            sheet.extend(Stroke::from_polyline(&frame, StrokeAttributes::new("frame")));
+
+            let baseline = Vec2::new(origin.x, corner.y + self.label_height * 1.5_f32 * scale);
+            font.print_in_drawing(label.clone(), baseline, self.label_height * scale, &StrokeAttributes::new("labels"), &mut sheet);
+        }
+
+        (sheet, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_keep_their_values_in_order() {
+        let spec = SweepSpec::parse("# comment\ncol = 4, 8,2\nsmooth_sharpness = 0.5\n").unwrap();
+        assert_eq!(spec.axes, vec![("col".to_owned(), vec![4_f32, 8_f32, 2_f32]), ("smooth_sharpness".to_owned(), vec![0.5_f32])]);
+        assert_eq!(spec.len(), 3);
+
+        // A setting given twice keeps its last values.
+        let spec = SweepSpec::parse("col = 4, 8\nrow = 1\ncol = 6").unwrap();
+        assert_eq!(spec.axes, vec![("row".to_owned(), vec![1_f32]), ("col".to_owned(), vec![6_f32])]);
+
+        let variations = SweepSpec::parse("col = 4, 8\nrow = 1, 2, 3").unwrap().variations(10, 2);
+        assert_eq!(variations.len(), 12);
+        assert_eq!(variations[0], Variation { seed: 10, settings: vec![("col".to_owned(), 4_f32), ("row".to_owned(), 1_f32)] });
+        assert_eq!(variations[1].settings[1], ("row".to_owned(), 2_f32));
+        assert_eq!(variations[6].seed, 11);
+    }
+
+    #[test]
+    fn ranges_end_on_their_last_step() {
+        let values = &SweepSpec::parse("smooth_sharpness = 0.1:0.9:0.1").unwrap().axes[0].1;
+        assert_eq!(values.len(), 9);
+        assert_eq!(values[0], 0.1_f32);
+        assert!((values[8] - 0.9_f32).abs() < 1e-5_f32);
+        assert!(values.windows(2).all(|pair| (pair[1] - pair[0] - 0.1_f32).abs() < 1e-5_f32));
+
+        // Adding 0.1 ten times overshoots 1, the last value is still there.
+        let values = &SweepSpec::parse("slice_percentage = 0:1:0.1").unwrap().axes[0].1;
+        assert_eq!(values.len(), 11);
+        assert!((values[10] - 1_f32).abs() < 1e-5_f32);
+
+        assert_eq!(SweepSpec::parse("col = 4 : 10 : 3").unwrap().axes[0].1, vec![4_f32, 7_f32, 10_f32]);
+        assert_eq!(SweepSpec::parse("col = 4:9:3").unwrap().axes[0].1, vec![4_f32, 7_f32]);
+        assert_eq!(SweepSpec::parse("col = 5:5:1").unwrap().axes[0].1, vec![5_f32]);
+    }
+
+    #[test]
+    fn malformed_specs_are_refused() {
+        for text in ["col = 4, x", "col = ", "col = 1:2", "col = 1:2:3:4", "col = 2:1:1", "col = 1:2:0", "col = 1:2:-1", "col = a:2:1", "col 4, 8", "= 4"] {
+            assert!(SweepSpec::parse(text).is_err(), "{} was accepted", text);
+        }
+
+        let message = SweepSpec::parse("tile_scale = 1:2").unwrap_err();
+        assert!(message.contains("tile_scale"), "{}", message);
+    }
+}
@@ -0,0 +1,233 @@
+#[cfg(feature = "viewer")]
+use ggez::*;
+use glam::*;
+
+use crate::drawing::*;
+use crate::error::*;
+use crate::font::*;
+use crate::import::*;
+use crate::hatching::*;
+#[cfg(feature = "raster")]
+use crate::density::*;
+use crate::config::*;
+use crate::generator::*;
+use crate::plotter::*;
+use crate::grid::*;
+use crate::batch::*;
+use crate::analysis::*;
+use crate::sketch::*;
+use crate::composer::*;
+#[cfg(feature = "viewer")]
+use crate::application::*;
+
+const HATCH_TARGETS : &str = "visited, random or imported";
+const IMPORT_MODES : &str = "frame, overlay or mask";
+const SWEEP : &str = "name=from:to";
+
+fn argument_error(flag: &str, expected: &str) -> Error {
+    Error::Argument { message: format!("{} expects {}", flag, expected) }
+}
+
+// The value following `flag`, None when the flag is not given.
+fn argument_value<'a>(arguments: &'a [String], flag: &str, expected: &str) -> Result<Option<&'a str>> {
+    match arguments.iter().position(|argument| argument == flag) {
+        Some(index) => arguments.get(index + 1).map(|value| Some(value.as_str())).ok_or_else(|| argument_error(flag, expected)),
+        None => Ok(None),
+    }
+}
+
+fn set_setting(composer: &mut Composer, name: &str, value: f32) -> Result<()> {
+    match composer.set_setting(name, value) {
+        true => Ok(()),
+        false => Err(Error::Argument { message: format!("No setting named {}", name) }),
+    }
+}
+
+/// Runs the command line, `arguments` starting with the program name.
+///
+/// Inputs given on the command line are required, the preview does not open without them.
+pub fn run(arguments: &[String]) -> Result<()> {
+    let scale = 5_f32;
+    let width = 150_f32;
+    let height = 100_f32;
+
+    let grid_parameters = GridParameters { col: 10, row: 10, tile_scale: 12_f32, shape: GridShape::Rectangle, subdivision_levels: 0, subdivision_scale: 0.15_f32 };
+
+    let mut parameters = ApplicationParameters{
+        animate_instructions: false,
+        plotter: PlotterParameters::default(),
+        playback_speed: 10_f32,
+        display_grid: false,
+        print_grid: false,
+        // Off until turned on with V.
+        simplification: None,
+        hatching: HatchParameters::default(),
+        analysis: AnalysisParameters::default(),
+        sketch: SketchParameters::default(),
+    };
+
+    let registry = GeneratorRegistry::with_defaults();
+    if arguments.iter().any(|argument| argument == "--list-generators") {
+        for name in registry.names() {
+            println!("{}", name);
+            for parameter in registry.create(name).into_iter().flat_map(|generator| generator.parameters()) {
+                let kind = match parameter.integer { true => "integer", false => "float" };
+                println!("    {} = {} ({} in [{}, {}])", parameter.name, parameter.value, kind, parameter.min, parameter.max);
+            }
+        }
+
+        return Ok(());
+    }
+
+    let generator_name = match argument_value(arguments, "--generator", "a generator name")? {
+        Some(name) => name,
+        None if arguments.iter().any(|argument| argument == "--flow-field") => "flow_field",
+        None => "hex_walk",
+    };
+
+    let generator = registry.create(generator_name)
+        .ok_or_else(|| Error::Argument { message: format!("Unknown generator {}, expected one of {:?}", generator_name, registry.names()) })?;
+
+    // Spacing, angle, pattern and probability of the hatching are settings, given with --param or in the config.
+    if let Some(target) = argument_value(arguments, "--hatch", HATCH_TARGETS)? {
+        parameters.hatching.target = Some(HatchTarget::parse(target).ok_or_else(|| argument_error("--hatch", HATCH_TARGETS))?);
+    }
+
+    let font_name = "Medias/HersheySans1.svgfont";
+    let font = Font::load(font_name)?;
+    let mut composer = Composer::new(grid_parameters, scale, Vec2::new(width, height), font, parameters, registry, generator);
+
+    // The config is watched while the preview is open, settings given on the command line only apply at startup.
+    let config_name = argument_value(arguments, "--config", "a config file")?;
+    if let Some(file_name) = config_name {
+        composer.apply_config(&Config::load(file_name)?);
+    }
+
+    #[cfg(feature = "raster")]
+    if let Some(file_name) = argument_value(arguments, "--density", "a png or jpeg file")? {
+        let density_map = DensityMap::load(file_name, &Bounds { min: Vec2::ZERO, max: Vec2::new(width, height) * scale })?;
+        composer.set_density_map(density_map);
+        composer.set_setting("density_bias", 2_f32);
+        composer.set_setting("density_revisits", 2_f32);
+        composer.set_setting("density_sharpness", 0.5_f32);
+    }
+
+    #[cfg(not(feature = "raster"))]
+    if argument_value(arguments, "--density", "a png or jpeg file")?.is_some() {
+        return Err(Error::Argument { message: "--density needs the raster feature, this binary was built without raster support".to_owned() });
+    }
+
+    // Each --param name=value overrides one setting, of the grid or of the selected generator.
+    for (index, _) in arguments.iter().enumerate().filter(|(_, argument)| *argument == "--param") {
+        let (name, value) = arguments.get(index + 1).and_then(|assignment| assignment.split_once('='))
+            .ok_or_else(|| argument_error("--param", "name=value"))?;
+        let value = parse_setting(value).ok_or_else(|| Error::Argument { message: format!("--param {} expects a number or a boolean", name) })?;
+        set_setting(&mut composer, name, value)?;
+
+        composer.rebuild_grid();
+    }
+
+    if let Some(seed) = argument_value(arguments, "--seed", "an unsigned integer")? {
+        composer.set_seed(seed.parse::<u64>().map_err(|_| argument_error("--seed", "an unsigned integer"))?);
+    }
+
+    if let Some(file_name) = argument_value(arguments, "--import", "a svg file")? {
+        let mode = match argument_value(arguments, "--import-mode", IMPORT_MODES)? {
+            Some(mode) => ImportMode::parse(mode).ok_or_else(|| argument_error("--import-mode", IMPORT_MODES))?,
+            None => ImportMode::Overlay,
+        };
+
+        let imported = import_svg(file_name, &StrokeAttributes::new("import"))?;
+        composer.set_import(imported, mode);
+    }
+
+    // A batch writes its variations and a contact sheet instead of opening the preview, the edition counter is left untouched.
+    let sweep_spec = match argument_value(arguments, "--sweep-spec", "a sweep file")? {
+        Some(file_name) => Some(SweepSpec::load(file_name)?),
+        None => None,
+    };
+
+    let batch_count = match argument_value(arguments, "--batch", "a number of variations")? {
+        Some(count) => Some(count.parse::<usize>().map_err(|_| argument_error("--batch", "a number of variations"))?),
+        None => None,
+    };
+
+    if batch_count.is_some() || sweep_spec.is_some() {
+        // With a sweep, --batch is the number of seeds of every combination, or of steps of --sweep.
+        let variations = match (sweep_spec, argument_value(arguments, "--sweep", SWEEP)?) {
+            (Some(_), Some(_)) => return Err(Error::Argument { message: String::from("--sweep and --sweep-spec cannot be combined") }),
+            (Some(sweep_spec), None) => sweep_spec.variations(composer.seed(), batch_count.unwrap_or(1)),
+            (None, Some(sweep)) => {
+                let (name, (from, to)) = sweep.split_once('=')
+                    .and_then(|(name, range)| Some((name, range.split_once(':')?)))
+                    .and_then(|(name, (from, to))| Some((name, (from.parse::<f32>().ok()?, to.parse::<f32>().ok()?))))
+                    .ok_or_else(|| argument_error("--sweep", SWEEP))?;
+
+                sweep_variations(composer.seed(), name, from, to, batch_count.unwrap_or(1))
+            },
+            (None, None) => seed_variations(composer.seed(), batch_count.unwrap_or(1)),
+        };
+
+        let directory = argument_value(arguments, "--batch-output", "a directory")?.unwrap_or("Exports/batch");
+
+        return composer.run_batch(&variations, &ContactSheet::default(), directory);
+    }
+
+    // Exports the drawing signed and numbered by the edition counter instead of opening the preview.
+    // --pick prints the variation of a batch made with that seed, its settings being given again with --param.
+    let pick = argument_value(arguments, "--pick", "the seed of a variation")?;
+    if let Some(seed) = pick {
+        composer.set_seed(seed.parse::<u64>().map_err(|_| argument_error("--pick", "the seed of a variation"))?);
+    }
+
+    if pick.is_some() || arguments.iter().any(|argument| argument == "--print") {
+        composer.generate(None)?;
+        println!("Exported {}", composer.print_to_svg()?);
+        return Ok(());
+    }
+
+    // Reports what plotting the drawing would take instead of opening the preview, signed when the signature can be read.
+    if arguments.iter().any(|argument| argument == "--analyze") {
+        if let Err(error) = composer.generate(None) {
+            println!("{}", error);
+        }
+
+        println!("{}", composer.analyze());
+        return Ok(());
+    }
+
+    open_preview(composer, font_name, config_name)
+}
+
+#[cfg(feature = "viewer")]
+fn open_preview(composer: Composer, font_name: &str, config_name: Option<&str>) -> Result<()> {
+    let mut c = conf::Conf::new();
+    c.window_mode.width = composer.size.x * composer.scale;
+    c.window_mode.height = composer.size.y * composer.scale;
+
+    let mut application = Application::new(composer);
+    application.watch_font(font_name);
+    if let Some(file_name) = config_name {
+        application.watch_config(file_name);
+    }
+
+    application.regenerate();
+    let (ctx, event_loop) = ContextBuilder::new("SVG Experiment", "AntonMakesGames")
+    .default_conf(c)
+    .window_setup(conf::WindowSetup{
+        title:String::from("SVG Generator"),
+        samples: conf::NumSamples::One,
+        vsync: true,
+        srgb:true,
+        icon:"".to_owned(),
+    })
+    .build()
+    .map_err(|source| Error::Viewer { source })?;
+
+    event::run(ctx, event_loop, application);
+}
+
+#[cfg(not(feature = "viewer"))]
+fn open_preview(_composer: Composer, _font_name: &str, _config_name: Option<&str>) -> Result<()> {
+    Err(Error::Argument { message: String::from("The preview needs the viewer feature, use --batch, --analyze, --print or --pick") })
+}
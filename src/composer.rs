@@ -0,0 +1,597 @@
+use glam::*;
+
+use crate::utils::*;
+use crate::drawing::*;
+use crate::font::*;
+use crate::signature::*;
+use crate::grid::*;
+use crate::simplify::*;
+use crate::import::*;
+use crate::hatching::*;
+use crate::generator::*;
+use crate::density::*;
+use crate::export::*;
+use crate::batch::*;
+use crate::analysis::*;
+use crate::error::*;
+use crate::config::*;
+use crate::plotter::*;
+use crate::sketch::*;
+use crate::noise::*;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::fs;
+
+#[derive(Copy, Clone)]
+pub struct ApplicationParameters {
+    pub display_grid : bool,
+    pub print_grid : bool,
+    /// Plays the plot simulation back after every generation, the strokes growing as the pen draws them.
+    pub animate_instructions : bool,
+    pub plotter : PlotterParameters,
+    /// How many times faster than the plotter the simulation plays.
+    pub playback_speed : f32,
+    pub simplification : Option<SimplificationParameters>,
+    pub hatching : HatchParameters,
+    pub analysis : AnalysisParameters,
+    pub sketch : SketchParameters,
+}
+
+// Everything a batch variation can change, restored before each variation and after the batch.
+struct Settings {
+    parameters : ApplicationParameters,
+    grid_parameters : GridParameters,
+    generator : Vec<Parameter>,
+    seed : u64,
+}
+
+// Tiles lighter than this are dropped when the grid is driven by a density map.
+const DENSITY_THRESHOLD : f32 = 0.05_f32;
+
+// Fixed so the subdivided grid stays the same from one seed to the next.
+const SUBDIVISION_NOISE_SEED : u64 = 0;
+
+// Distance in millimetres the plot keeps from the edges of the page.
+const SAFE_MARGIN : f32 = 5_f32;
+
+/// Builds the drawing from the grid, the generator, the imported svg, the hatching and the sketched strokes,
+/// and exports, analyses or batches it, without needing the preview.
+pub struct Composer {
+    pub(crate) grid: Grid,
+    pub(crate) grid_parameters: GridParameters,
+    density_map: Option<DensityMap>,
+    pub(crate) scale: f32,
+    pub(crate) size: Vec2,
+    pub(crate) font : Font,
+
+    pub(crate) parameters : ApplicationParameters,
+    pub(crate) registry : GeneratorRegistry,
+    pub(crate) generator : Box<dyn Generator>,
+    pub(crate) seed : u64,
+    pub(crate) tile_constraints : TileConstraints,
+
+    pub(crate) drawing: Drawing,
+    // Strokes drawn by hand, added on top of everything generated.
+    pub(crate) sketch: Drawing,
+    imported: Drawing,
+    import_mode: Option<ImportMode>,
+}
+
+impl Composer {
+    pub fn new(grid_parameters: GridParameters, scale: f32, size: Vec2, font : Font, parameters : ApplicationParameters, registry : GeneratorRegistry, generator : Box<dyn Generator>) -> Composer {
+        Composer {
+            grid: Grid::centered_hex_grid(&grid_parameters, size * scale),
+            grid_parameters,
+            density_map: None,
+            scale,
+            size,
+            font,
+            parameters,
+            registry,
+            generator,
+            seed: rand::random(),
+            tile_constraints: TileConstraints::default(),
+            drawing: Drawing::new(),
+            sketch: Drawing::new(),
+            imported: Drawing::new(),
+            import_mode: None,
+        }
+    }
+
+    pub(crate) fn simplified_drawing(&self) -> Drawing {
+        match self.parameters.simplification {
+            Some(simplification) => {
+                let mut statistics = SimplificationStatistics::default();
+                let simplified = simplify_drawing(&self.drawing, simplification.method, simplification.tolerance * self.scale, &mut statistics);
+                println!("Simplification {:?} : {}", simplification.method, statistics);
+                simplified
+            },
+            None => self.drawing.clone(),
+        }
+    }
+
+    /// What gets exported, the grid if printed and the simplified drawing.
+    pub fn export_drawing(&self) -> Drawing {
+        let mut drawing = Drawing::new();
+        if self.parameters.print_grid {
+            self.grid.print_to_drawing(&StrokeAttributes::new("grid"), &mut drawing);
+        }
+
+        drawing.append(self.simplified_drawing());
+        drawing
+    }
+
+    pub fn analyze(&self) -> DrawingAnalysis {
+        DrawingAnalysis::new(&self.export_drawing(), self.scale, &self.page_bounds(), &self.parameters.analysis)
+    }
+
+    /// Writes the export numbered by the signature counter and returns its file name.
+    pub fn print_to_svg(&self) -> Result<String> {
+        let drawing = self.export_drawing();
+        let simulation = PlotSimulation::new(&drawing, self.scale, &self.parameters.plotter);
+        println!("Estimated plot time : {} ({:.0} mm drawn, {:.0} mm of travel, {} pen lifts)", format_duration(simulation.duration), simulation.pen_down_length, simulation.pen_up_length, simulation.pen_lifts);
+
+        let export_name = format!("Exports/AMG_{}.svg",get_signature_counter()?);
+
+        save_svg(&drawing, self.size, self.scale, &export_name)?;
+        increment_signature_counter()?;
+        Ok(export_name)
+    }
+
+    pub fn page_bounds(&self) -> Bounds {
+        Bounds { min: Vec2::ZERO, max: self.size * self.scale }
+    }
+
+    pub fn safe_area(&self) -> Bounds {
+        let margin = Vec2::splat(SAFE_MARGIN * self.scale);
+        Bounds { min: margin, max: self.size * self.scale - margin }
+    }
+
+    /// Rebuilds the grid from its parameters, then subdivides it and applies the density map and mask again.
+    pub fn rebuild_grid(&mut self) {
+        self.grid = Grid::centered_hex_grid(&self.grid_parameters, self.size * self.scale);
+        if !self.tile_constraints.is_empty() {
+            println!("The grid changed, tile edits are cleared");
+            self.tile_constraints = TileConstraints::default();
+        }
+
+        if let Some(density_map) = &mut self.density_map {
+            density_map.fit(&self.grid.bounds());
+        }
+
+        self.subdivide_grid();
+        if let Some(density_map) = &self.density_map {
+            self.grid.apply_density(|position| density_map.sample(position), DENSITY_THRESHOLD);
+        }
+
+        if let Some(mode) = self.import_mode {
+            let imported = std::mem::take(&mut self.imported);
+            self.set_import(imported, mode);
+        }
+    }
+
+    // Tiles are split where the density map is darker, or where the noise is higher without one,
+    // each level needing a darker or higher value than the previous one.
+    fn subdivide_grid(&mut self) {
+        let levels = self.grid_parameters.subdivision_levels;
+        if levels == 0 {
+            return;
+        }
+
+        let noise = Perlin::new(&mut StdRng::seed_from_u64(SUBDIVISION_NOISE_SEED));
+        let frequency = self.grid_parameters.subdivision_scale / self.grid_parameters.tile_scale;
+        let density_map = self.density_map.as_ref();
+        self.grid.subdivide(levels, |tile| {
+            let value = match density_map {
+                Some(density_map) => density_map.sample(tile.position),
+                None => (noise.noise(tile.position * frequency) + 1_f32) / 2_f32,
+            };
+
+            value > (tile.level + 1) as f32 / (levels + 1) as f32
+        });
+    }
+
+    #[cfg(feature = "raster")]
+    pub fn set_density_map(&mut self, mut density_map: DensityMap) {
+        density_map.fit(&self.grid.bounds());
+        // The map decides where tiles are split, so a subdivided grid is built again.
+        if self.grid_parameters.subdivision_levels > 0 {
+            self.density_map = Some(density_map);
+            self.rebuild_grid();
+            return;
+        }
+
+        self.grid.apply_density(|position| density_map.sample(position), DENSITY_THRESHOLD);
+        self.density_map = Some(density_map);
+    }
+
+    pub fn set_import(&mut self, mut imported: Drawing, mode: ImportMode) {
+        let target = match mode {
+            ImportMode::Frame => {
+                self.safe_area()
+            },
+            ImportMode::Overlay | ImportMode::Mask => self.grid.bounds(),
+        };
+
+        fit_drawing(&mut imported, &target);
+        if mode == ImportMode::Mask {
+            self.grid.retain_tiles(|tile| is_inside(&imported, tile.position));
+        }
+
+        self.imported = imported;
+        self.import_mode = Some(mode);
+    }
+
+    fn import_into_drawing(&mut self) {
+        match self.import_mode {
+            Some(ImportMode::Frame) | Some(ImportMode::Overlay) => self.drawing.append(self.imported.clone()),
+            _ => (),
+        }
+    }
+
+    /// Returns false when no generator is registered under that name.
+    pub fn set_generator(&mut self, name: &str) -> bool {
+        match self.registry.create(name) {
+            Some(generator) => {
+                self.generator = generator;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // With a region, only what goes through those tiles is generated again, from a fresh seed.
+    fn generate_into_drawing(&mut self, region: Option<&[usize]>) {
+        let mask = match self.import_mode {
+            Some(ImportMode::Mask) => Some(&self.imported),
+            _ => None,
+        };
+
+        let context = GeneratorContext { grid: &self.grid, bounds: self.grid.bounds(), scale: self.scale, mask, constraints: &self.tile_constraints };
+        if let Some(region) = region {
+            let seed = rand::random();
+            if self.generator.generate_region(seed, &context, region, &mut self.drawing) {
+                println!("Regenerated {} tiles with seed {}", region.len(), seed);
+                return;
+            }
+
+            println!("{} cannot regenerate a region, regenerating everything", self.generator.name());
+        }
+
+        self.generator.generate(self.seed, &context, &mut self.drawing);
+    }
+
+    fn hatch_into_drawing(&mut self) {
+        let hatching = self.parameters.hatching;
+        let Some(target) = hatching.target else { return };
+        let attributes = StrokeAttributes::new("hatching");
+        let spacing = hatching.spacing * self.scale;
+
+        let polygons : Vec<Vec<Vec2>> = match target {
+            HatchTarget::VisitedTiles => self.generator.visited_tiles().iter().map(|index| self.grid.tiles[*index].vertices.clone()).collect(),
+            HatchTarget::RandomTiles => {
+                // Offset so the shaded tiles do not correlate with the generator's own draws.
+                let mut random = StdRng::seed_from_u64(self.seed.wrapping_add(1));
+                self.grid.tiles.iter().filter(|_| random.gen::<f32>() < hatching.probability).map(|tile| tile.vertices.clone()).collect()
+            },
+            HatchTarget::ImportedShapes => self.imported.iter().filter(|stroke| stroke.attributes.closed).map(|stroke| {
+                let mut points = stroke.flattened_points(8);
+                points.pop();
+                points
+            }).collect(),
+        };
+
+        for polygon in polygons {
+            hatch_polygon(&polygon, hatching.pattern, spacing, hatching.angle, &attributes, &mut self.drawing);
+        }
+    }
+
+    fn sign_into_instructions(&mut self) -> Result<()> {
+        let signature = get_signature()?;
+        let signature_height = 9.0_f32;
+        let signature_width = self.font.get_width(signature.clone(), signature_height);
+        let signature_margine = 15_f32;
+        self.font.print_in_drawing(signature, Vec2::new(self.size.x * self.scale - signature_width - signature_margine, self.size.y * self.scale - 3_f32), signature_height, &StrokeAttributes::new("signature"), &mut self.drawing);
+        Ok(())
+    }
+
+    /// Sets a grid, display or generator setting by name, returns false when nothing has that name.
+    /// The grid is not rebuilt and nothing is regenerated.
+    pub fn set_setting(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "display_grid" => self.parameters.display_grid = value > 0.5_f32,
+            "print_grid" => self.parameters.print_grid = value > 0.5_f32,
+            "col" => self.grid_parameters.col = value.round().max(1_f32) as usize,
+            "row" => self.grid_parameters.row = value.round().max(1_f32) as usize,
+            "tile_scale" => self.grid_parameters.tile_scale = value.max(1_f32),
+            "hexagon_grid" => self.toggle_grid_shape(GridShape::Hexagon, value > 0.5_f32),
+            "square_grid" => self.toggle_grid_shape(GridShape::Square, value > 0.5_f32),
+            "subdivision_levels" => self.grid_parameters.subdivision_levels = value.round().max(0_f32) as usize,
+            "subdivision_scale" => self.grid_parameters.subdivision_scale = value.max(0.001_f32),
+            "animate_instructions" => self.parameters.animate_instructions = value > 0.5_f32,
+            "pen_down_speed" => self.parameters.plotter.pen_down_speed = value.max(0.1_f32),
+            "pen_up_speed" => self.parameters.plotter.pen_up_speed = value.max(0.1_f32),
+            "acceleration" => self.parameters.plotter.acceleration = value.max(1_f32),
+            "pen_lift_duration" => self.parameters.plotter.pen_lift_duration = value.max(0_f32),
+            "playback_speed" => self.parameters.playback_speed = value.max(0.25_f32),
+            "ink_per_metre" => self.parameters.analysis.ink_per_metre = value.max(0_f32),
+            // A negative target turns hatching off.
+            "hatch_target" => self.parameters.hatching.target = match value < 0_f32 {
+                true => None,
+                false => Some(HatchTarget::from_index(value.round() as usize)),
+            },
+            "hatch_pattern" => self.parameters.hatching.pattern = HatchPattern::from_index(value.round().max(0_f32) as usize),
+            "hatch_spacing" => self.parameters.hatching.spacing = value.max(0.05_f32),
+            "hatch_angle" => self.parameters.hatching.angle = value,
+            "hatch_probability" => self.parameters.hatching.probability = value.clamp(0_f32, 1_f32),
+            // A negative mode or a null tolerance disables smoothing or simplification of the sketch.
+            "sketch_smoothing_mode" => self.parameters.sketch.smoothing_mode = match value < 0_f32 {
+                true => None,
+                false => Some(SmoothingMode::from_index(value.round() as usize)),
+            },
+            "sketch_smooth_number_of_points" => self.parameters.sketch.smooth_number_of_points = value.round().max(1_f32) as usize,
+            "sketch_smooth_sharpness" => self.parameters.sketch.smooth_sharpness = value,
+            "sketch_tolerance" => self.parameters.sketch.simplification = match value > 0_f32 {
+                true => Some(SimplificationParameters { method: SimplificationMethod::RamerDouglasPeucker, tolerance: value }),
+                false => None,
+            },
+            _ => return self.generator.set_parameter(name, value),
+        }
+
+        true
+    }
+
+    /// Value of a setting as `set_setting` takes it, booleans being 1 or 0, None when nothing has that name.
+    pub fn setting(&self, name: &str) -> Option<f32> {
+        let flag = |value: bool| match value { true => 1_f32, false => 0_f32 };
+        Some(match name {
+            "display_grid" => flag(self.parameters.display_grid),
+            "print_grid" => flag(self.parameters.print_grid),
+            "col" => self.grid_parameters.col as f32,
+            "row" => self.grid_parameters.row as f32,
+            "tile_scale" => self.grid_parameters.tile_scale,
+            "hexagon_grid" => flag(self.grid_parameters.shape == GridShape::Hexagon),
+            "square_grid" => flag(self.grid_parameters.shape == GridShape::Square),
+            "subdivision_levels" => self.grid_parameters.subdivision_levels as f32,
+            "subdivision_scale" => self.grid_parameters.subdivision_scale,
+            "animate_instructions" => flag(self.parameters.animate_instructions),
+            "pen_down_speed" => self.parameters.plotter.pen_down_speed,
+            "pen_up_speed" => self.parameters.plotter.pen_up_speed,
+            "acceleration" => self.parameters.plotter.acceleration,
+            "pen_lift_duration" => self.parameters.plotter.pen_lift_duration,
+            "playback_speed" => self.parameters.playback_speed,
+            "ink_per_metre" => self.parameters.analysis.ink_per_metre,
+            "hatch_target" => self.parameters.hatching.target.map_or(-1_f32, |target| target.index() as f32),
+            "hatch_pattern" => self.parameters.hatching.pattern.index() as f32,
+            "hatch_spacing" => self.parameters.hatching.spacing,
+            "hatch_angle" => self.parameters.hatching.angle,
+            "hatch_probability" => self.parameters.hatching.probability,
+            "sketch_smoothing_mode" => self.parameters.sketch.smoothing_mode.map_or(-1_f32, |mode| mode.index() as f32),
+            "sketch_smooth_number_of_points" => self.parameters.sketch.smooth_number_of_points as f32,
+            "sketch_smooth_sharpness" => self.parameters.sketch.smooth_sharpness,
+            "sketch_tolerance" => self.parameters.sketch.simplification.map_or(0_f32, |simplification| simplification.tolerance),
+            _ => return self.generator.parameter(name),
+        })
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            parameters: self.parameters,
+            grid_parameters: self.grid_parameters,
+            generator: self.generator.parameters(),
+            seed: self.seed,
+        }
+    }
+
+    fn restore_settings(&mut self, settings: &Settings) {
+        self.parameters = settings.parameters;
+        for parameter in &settings.generator {
+            self.generator.set_parameter(parameter.name, parameter.value);
+        }
+
+        self.seed = settings.seed;
+        if self.grid_parameters != settings.grid_parameters {
+            self.grid_parameters = settings.grid_parameters;
+            self.rebuild_grid();
+        }
+    }
+
+    // Turning a shape off only goes back to the rectangle when that shape is the current one,
+    // so the order of the settings of a config does not matter.
+    fn toggle_grid_shape(&mut self, shape: GridShape, enabled: bool) {
+        if enabled {
+            self.grid_parameters.shape = shape;
+        }
+        else if self.grid_parameters.shape == shape {
+            self.grid_parameters.shape = GridShape::Rectangle;
+        }
+    }
+
+    // Applies every setting of the config, switching generator first when it names another one.
+    pub(crate) fn apply_config(&mut self, config: &Config) {
+        if let Some(name) = config.get("generator") {
+            if name != self.generator.name() && !self.set_generator(name) {
+                println!("Config : unknown generator {}, expected one of {:?}", name, self.registry.names());
+            }
+        }
+
+        let grid_parameters = self.grid_parameters;
+        for (name, value) in config.entries().filter(|(name, _)| *name != "generator") {
+            match parse_setting(value) {
+                Some(value) if self.set_setting(name, value) => (),
+                Some(_) => println!("Config : unknown setting {}", name),
+                None => println!("Config : {} expects a number or a boolean, found {}", name, value),
+            }
+        }
+
+        if self.grid_parameters != grid_parameters {
+            self.rebuild_grid();
+        }
+    }
+
+    /// Composes and signs the drawing. With a region, only what goes through those tiles is generated again.
+    /// The drawing is composed even when signing it fails.
+    pub fn generate(&mut self, region: Option<&[usize]>) -> Result<()> {
+        self.compose_drawing(region);
+        self.sign_into_instructions()
+    }
+
+    // Everything but the signature.
+    fn compose_drawing(&mut self, region: Option<&[usize]>) {
+        self.drawing.clear();
+        self.import_into_drawing();
+        self.generate_into_drawing(region);
+        self.hatch_into_drawing();
+        self.drawing.append(self.sketch.clone());
+    }
+
+    /// Exported drawing of a batch variation, unsigned so no edition number is used.
+    /// Settings the variation does not name keep the values of `settings`.
+    fn generate_variation(&mut self, variation: &Variation, settings: &Settings) -> Drawing {
+        self.restore_settings(settings);
+        let grid_parameters = self.grid_parameters;
+        for (name, value) in &variation.settings {
+            self.set_setting(name, *value);
+        }
+
+        if self.grid_parameters != grid_parameters {
+            self.rebuild_grid();
+        }
+
+        self.seed = variation.seed;
+        self.compose_drawing(None);
+        self.export_drawing()
+    }
+
+    /// Writes every variation, a contact sheet of them all and a csv of their metrics in `directory`.
+    ///
+    /// Each variation starts from the settings the composer had before the batch, which it has again afterwards.
+    /// Nothing is written when a variation names an unknown setting.
+    pub fn run_batch(&mut self, variations: &[Variation], sheet: &ContactSheet, directory: &str) -> Result<()> {
+        // One column per setting changed by any variation, in order of appearance.
+        let mut setting_names : Vec<&str> = Vec::new();
+        for (name, _) in variations.iter().flat_map(|variation| &variation.settings) {
+            if !setting_names.contains(&name.as_str()) {
+                setting_names.push(name);
+            }
+        }
+
+        if let Some(name) = setting_names.iter().find(|name| self.setting(name).is_none()) {
+            return Err(Error::Argument { message: format!("No setting named {}", name) });
+        }
+
+        fs::create_dir_all(directory).map_err(|error| Error::io(directory, error))?;
+        let settings = self.settings();
+        let written = self.write_variations(variations, &setting_names, &settings, sheet, directory);
+        self.restore_settings(&settings);
+        written
+    }
+
+    fn write_variations(&mut self, variations: &[Variation], setting_names: &[&str], settings: &Settings, sheet: &ContactSheet, directory: &str) -> Result<()> {
+        let mut csv = String::from("file,seed");
+        for name in setting_names {
+            csv.push_str(&format!(",{}", name));
+        }
+        csv.push_str(&format!(",{}\n", DrawingMetrics::CSV_HEADER));
+
+        let mut thumbnails = Vec::new();
+        for (variation, file_stem) in variations.iter().zip(file_stems(variations)) {
+            let drawing = self.generate_variation(variation, settings);
+            let file_name = format!("{}/{}.svg", directory, file_stem);
+            save_svg(&drawing, self.size, self.scale, &file_name)?;
+            println!("Batch : {} -> {}", variation.label(), file_name);
+
+            csv.push_str(&format!("{}.svg,{}", file_stem, variation.seed));
+            // The values the variation was drawn with, clamped by the settings and taken from before the batch when not named.
+            for name in setting_names {
+                csv.push_str(&format!(",{}", self.setting(name).unwrap_or_default()));
+            }
+            csv.push_str(&format!(",{}\n", DrawingMetrics::measure(&drawing, self.scale).csv_row()));
+
+            thumbnails.push((drawing, variation.label()));
+        }
+
+        let metrics_name = format!("{}/metrics.csv", directory);
+        fs::write(&metrics_name, csv).map_err(|error| Error::io(&metrics_name, error))?;
+
+        let (contact_sheet, size) = sheet.layout(&thumbnails, self.size, self.scale, &self.font);
+        let file_name = format!("{}/contact_sheet", directory);
+        save_svg(&contact_sheet, size, self.scale, &format!("{}.svg", file_name))?;
+        #[cfg(feature = "raster")]
+        save_png(&contact_sheet, size, self.scale, 4_f32, &format!("{}.png", file_name))?;
+        println!("Batch : contact sheet written to {}.svg", file_name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn composer(directory: &std::path::Path) -> Composer {
+        let font_name = directory.join("font.svgfont");
+        fs::write(&font_name, r#"<svg><defs><font><font-face units-per-em="1000"/></font></defs></svg>"#).unwrap();
+        let font = Font::load(font_name.to_str().unwrap()).unwrap();
+
+        let grid_parameters = GridParameters { col: 5, row: 5, tile_scale: 10_f32, shape: GridShape::Rectangle, subdivision_levels: 0, subdivision_scale: 0.15_f32 };
+        let parameters = ApplicationParameters {
+            display_grid: false,
+            print_grid: false,
+            animate_instructions: false,
+            plotter: PlotterParameters::default(),
+            playback_speed: 10_f32,
+            simplification: None,
+            hatching: HatchParameters::default(),
+            analysis: AnalysisParameters::default(),
+            sketch: SketchParameters::default(),
+        };
+
+        let registry = GeneratorRegistry::with_defaults();
+        let generator = registry.create("hex_walk").unwrap();
+        let mut composer = Composer::new(grid_parameters, 5_f32, Vec2::new(150_f32, 100_f32), font, parameters, registry, generator);
+        composer.set_seed(7);
+        composer
+    }
+
+    fn variation(seed: u64, settings: &[(&str, f32)]) -> Variation {
+        Variation { seed, settings: settings.iter().map(|(name, value)| (name.to_string(), *value)).collect() }
+    }
+
+    #[test]
+    fn batches_start_every_variation_from_the_settings_before_them() {
+        let directory = std::env::temp_dir().join(format!("svg_experiments_batch_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut composer = composer(&directory);
+        let output = directory.join("batch");
+
+        let variations = [variation(1, &[("col", 3_f32), ("smooth_sharpness", 0.2_f32)]), variation(2, &[("row", 4_f32)])];
+        composer.run_batch(&variations, &ContactSheet::default(), output.to_str().unwrap()).unwrap();
+        let csv = fs::read_to_string(output.join("metrics.csv")).unwrap();
+
+        // Settings a variation does not name are written with the values it was drawn with.
+        let rows : Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').take(5).collect()).collect();
+        assert_eq!(rows[0], vec!["file", "seed", "col", "smooth_sharpness", "row"]);
+        assert_eq!(rows[1][1..], ["1", "3", "0.2", "5"]);
+        assert_eq!(rows[2][1..], ["2", "5", "0.9", "4"]);
+
+        assert_eq!(composer.setting("col"), Some(5_f32));
+        assert_eq!(composer.setting("row"), Some(5_f32));
+        assert_eq!(composer.setting("smooth_sharpness"), Some(0.9_f32));
+        assert_eq!(composer.seed(), 7);
+
+        let unknown = [variation(1, &[("col", 3_f32)]), variation(2, &[("smoth_sharpness", 0.2_f32)])];
+        let skipped = directory.join("skipped");
+        assert!(matches!(composer.run_batch(&unknown, &ContactSheet::default(), skipped.to_str().unwrap()), Err(Error::Argument { .. })));
+        assert!(!skipped.exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs;
+#[cfg(feature = "viewer")]
+use std::time::SystemTime;
+
+use crate::error::Error;
+
+/// Settings read from a text file of `name = value` lines, `#` starting a comment.
+pub struct Config {
+    entries : Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut entries = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((name, value)) if !name.trim().is_empty() => entries.push((name.trim().to_owned(), value.trim().to_owned())),
+                _ => return Err(format!("line {} : expected name = value, found \"{}\"", line_index + 1, line)),
+            }
+        }
+
+        Ok(Config { entries })
+    }
+
+    pub fn load(file_name: &str) -> crate::error::Result<Config> {
+        let text = fs::read_to_string(file_name).map_err(|error| Error::io(file_name, error))?;
+        Config::parse(&text).map_err(|message| Error::Config { file_name: file_name.to_owned(), message })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter().rev().find(|(other, _)| other == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Numbers as they are, booleans as 1 or 0.
+pub fn parse_setting(value: &str) -> Option<f32> {
+    match value {
+        "true" => Some(1_f32),
+        "false" => Some(0_f32),
+        _ => value.parse::<f32>().ok(),
+    }
+}
+
+/// Polls the modification time of a file.
+#[cfg(feature = "viewer")]
+pub struct FileWatcher {
+    pub file_name : String,
+    modified : Option<SystemTime>,
+}
+
+#[cfg(feature = "viewer")]
+impl FileWatcher {
+    pub fn new(file_name: &str) -> FileWatcher {
+        FileWatcher { file_name: file_name.to_owned(), modified: FileWatcher::modified(file_name) }
+    }
+
+    fn modified(file_name: &str) -> Option<SystemTime> {
+        fs::metadata(file_name).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    /// True once per change, a file that went missing is not a change.
+    pub fn has_changed(&mut self) -> bool {
+        match FileWatcher::modified(&self.file_name) {
+            Some(modified) if Some(modified) != self.modified => {
+                self.modified = Some(modified);
+                true
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use glam::*;
+
+use crate::drawing::*;
+use crate::error::*;
+
+/// Darkness of a grayscale picture stretched over a region of the drawing.
+pub struct DensityMap {
+    width : u32,
+    height : u32,
+    // Row major luminance, 0 for black.
+    pixels : Vec<u8>,
+    bounds : Bounds,
+}
+
+impl DensityMap {
+    /// Grayscale pixels of a `width` by `height` picture, fitted inside `target`.
+    ///
+    /// Fails when the picture is empty or `pixels` does not hold one byte per pixel.
+    pub fn from_luma(width: u32, height: u32, pixels: Vec<u8>, target: &Bounds) -> Result<DensityMap> {
+        let pixel_count = (width as usize).checked_mul(height as usize);
+        if width == 0 || height == 0 || pixel_count != Some(pixels.len()) {
+            return Err(Error::Density { width, height, byte_count: pixels.len() });
+        }
+
+        let mut density_map = DensityMap { width, height, pixels, bounds: *target };
+        density_map.fit(target);
+        Ok(density_map)
+    }
+
+    /// Decodes a png or jpeg picture, fitted inside `target`.
+    #[cfg(feature = "raster")]
+    pub fn load(file_name: &str, target: &Bounds) -> Result<DensityMap> {
+        let image = image::open(file_name)
+            .map_err(|source| Error::Image { file_name: file_name.to_owned(), source })?
+            .to_luma8();
+
+        DensityMap::from_luma(image.width(), image.height(), image.into_raw(), target)
+    }
+
+    /// The image keeps its aspect ratio and is centered inside `target`.
+    pub fn fit(&mut self, target: &Bounds) {
+        let image_size = Vec2::new(self.width as f32, self.height as f32);
+        let target_size = target.size();
+        let scale = (target_size.x / image_size.x).min(target_size.y / image_size.y);
+        let size = image_size * scale;
+
+        self.bounds = Bounds { min: target.center() - size / 2_f32, max: target.center() + size / 2_f32 };
+    }
+
+    /// 0 for white or outside of the image, 1 for black, bilinearly interpolated.
+    pub fn sample(&self, position: Vec2) -> f32 {
+        if !self.bounds.contains(position) {
+            return 0_f32;
+        }
+
+        let max = Vec2::new((self.width - 1) as f32, (self.height - 1) as f32);
+        let pixel = ((position - self.bounds.min) / self.bounds.size() * max).clamp(Vec2::ZERO, max);
+        let x0 = pixel.x.floor() as u32;
+        let y0 = pixel.y.floor() as u32;
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let fraction = pixel - Vec2::new(x0 as f32, y0 as f32);
+
+        let darkness = |x: u32, y: u32| 1_f32 - self.pixels[(y * self.width + x) as usize] as f32 / 255_f32;
+        let top = darkness(x0, y0) + (darkness(x1, y0) - darkness(x0, y0)) * fraction.x;
+        let bottom = darkness(x0, y1) + (darkness(x1, y1) - darkness(x0, y1)) * fraction.x;
+
+        top + (bottom - top) * fraction.y
+    }
+}
@@ -0,0 +1,298 @@
+use glam::*;
+
+use crate::utils::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StrokeAttributes {
+    pub layer : String,
+    pub stroke_width : f32,
+    pub color : String,
+    pub closed : bool,
+}
+
+impl StrokeAttributes {
+    pub fn new(layer : &str) -> StrokeAttributes {
+        StrokeAttributes {
+            layer: layer.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for StrokeAttributes {
+    fn default() -> StrokeAttributes {
+        StrokeAttributes {
+            layer: "default".to_owned(),
+            stroke_width: 0.4_f32,
+            color: "black".to_owned(),
+            closed: false,
+        }
+    }
+}
+
+/// A continuous pen-down movement : a single `MoveTo` followed by lines and curves.
+#[derive(Clone)]
+pub struct Stroke {
+    instructions : Vec<Instruction>,
+    pub attributes : StrokeAttributes,
+}
+
+impl Stroke {
+    pub fn new(start : Vec2, attributes : StrokeAttributes) -> Stroke {
+        Stroke {
+            instructions: vec![Instruction::MoveTo(start)],
+            attributes,
+        }
+    }
+
+    /// None for an empty list of positions.
+    pub fn from_polyline(positions : &[Vec2], attributes : StrokeAttributes) -> Option<Stroke> {
+        let (first, rest) = positions.split_first()?;
+        let mut stroke = Stroke::new(*first, attributes);
+        for position in rest {
+            stroke.line_to(*position);
+        }
+
+        Some(stroke)
+    }
+
+    pub fn line_to(&mut self, position : Vec2) {
+        self.instructions.push(Instruction::LineTo(position));
+    }
+
+    pub fn quad_to(&mut self, control : Vec2, position : Vec2) {
+        self.instructions.push(Instruction::QuadTo(control, position));
+    }
+
+    pub fn cubic_to(&mut self, control1 : Vec2, control2 : Vec2, position : Vec2) {
+        self.instructions.push(Instruction::CubicTo(control1, control2, position));
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn start(&self) -> Vec2 {
+        self.instructions[0].end_point()
+    }
+
+    pub fn end(&self) -> Vec2 {
+        match self.attributes.closed {
+            true => self.start(),
+            false => self.instructions[self.instructions.len() - 1].end_point(),
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.instructions.len() - 1
+    }
+
+    /// Every point of the stroke with curves split in `nb_points` segments, closing point included.
+    pub fn flattened_points(&self, nb_points : usize) -> Vec<Vec2> {
+        let mut points : Vec<Vec2> = flatten_instructions(&self.instructions, nb_points).iter().map(|instruction| instruction.end_point()).collect();
+        if self.attributes.closed {
+            points.push(self.start());
+        }
+
+        points
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(&self.flattened_points(8))
+    }
+
+    pub fn transform(&mut self, transform : &Affine2) {
+        for instruction in &mut self.instructions {
+            *instruction = instruction.map(|position| transform.transform_point2(position));
+        }
+    }
+}
+
+/// Axis aligned bounding box.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Bounds {
+    pub min : Vec2,
+    pub max : Vec2,
+}
+
+impl Bounds {
+    /// Empty bounds at the origin when there is no point.
+    pub fn from_points(points : &[Vec2]) -> Bounds {
+        let Some((first, rest)) = points.split_first() else {
+            return Bounds { min: Vec2::ZERO, max: Vec2::ZERO };
+        };
+
+        let mut bounds = Bounds { min: *first, max: *first };
+        for point in rest {
+            bounds.min = bounds.min.min(*point);
+            bounds.max = bounds.max.max(*point);
+        }
+
+        bounds
+    }
+
+    pub fn union(&self, other : &Bounds) -> Bounds {
+        Bounds { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) / 2_f32
+    }
+
+    pub fn contains(&self, point : Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+}
+
+/// Strokes in plotting order, grouped in layers by their attributes.
+#[derive(Clone, Default)]
+pub struct Drawing {
+    pub strokes : Vec<Stroke>,
+}
+
+impl Drawing {
+    pub fn new() -> Drawing {
+        Drawing { strokes: Vec::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+
+    pub fn add(&mut self, stroke : Stroke) {
+        self.strokes.push(stroke);
+    }
+
+    pub fn append(&mut self, other : Drawing) {
+        self.strokes.extend(other.strokes);
+    }
+
+    pub fn retain(&mut self, keep : impl FnMut(&Stroke) -> bool) {
+        self.strokes.retain(keep);
+    }
+
+    /// Splits a flat instruction list into strokes at every `MoveTo`, dropping moves that draw nothing.
+    pub fn add_instructions(&mut self, instructions : &[Instruction], attributes : &StrokeAttributes) {
+        let mut current_position = Vec2::ZERO;
+        let mut stroke : Option<Stroke> = None;
+
+        for instruction in instructions {
+            match instruction {
+                Instruction::MoveTo(pos) => {
+                    if let Some(stroke) = stroke.take() {
+                        if stroke.segment_count() > 0 {
+                            self.add(stroke);
+                        }
+                    }
+
+                    current_position = *pos;
+                },
+                _ => {
+                    stroke.get_or_insert_with(|| Stroke::new(current_position, attributes.clone())).instructions.push(*instruction);
+                    current_position = instruction.end_point();
+                },
+            }
+        }
+
+        if let Some(stroke) = stroke {
+            self.add(stroke);
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Stroke> {
+        self.strokes.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Stroke> {
+        self.strokes.iter_mut()
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.strokes.iter().flat_map(|stroke| stroke.instructions.iter())
+    }
+
+    pub fn layer<'a>(&'a self, layer : &'a str) -> impl Iterator<Item = &'a Stroke> {
+        self.strokes.iter().filter(move |stroke| stroke.attributes.layer == layer)
+    }
+
+    /// Layer names in order of first appearance.
+    pub fn layers(&self) -> Vec<String> {
+        let mut layers : Vec<String> = Vec::new();
+        for stroke in &self.strokes {
+            if !layers.contains(&stroke.attributes.layer) {
+                layers.push(stroke.attributes.layer.clone());
+            }
+        }
+
+        layers
+    }
+
+    /// Strokes in the order they are plotted, layer after layer.
+    pub fn plot_order(&self) -> Vec<&Stroke> {
+        self.layers().into_iter().flat_map(|layer| self.strokes.iter().filter(move |stroke| stroke.attributes.layer == layer)).collect()
+    }
+
+    /// Moves with the pen up between consecutive strokes in plot order.
+    pub fn pen_up_moves(&self) -> Vec<(Vec2, Vec2)> {
+        let strokes = self.plot_order();
+        strokes.windows(2)
+            .map(|pair| (pair[0].end(), pair[1].start()))
+            .filter(|(from, to)| from.distance_squared(*to) > f32::EPSILON)
+            .collect()
+    }
+
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.strokes.iter().map(|stroke| stroke.bounds()).reduce(|a, b| a.union(&b))
+    }
+
+    pub fn transform(&mut self, transform : &Affine2) {
+        for stroke in &mut self.strokes {
+            stroke.transform(transform);
+        }
+    }
+
+    pub fn translate(&mut self, offset : Vec2) {
+        self.transform(&Affine2::from_translation(offset));
+    }
+
+    pub fn scale(&mut self, scale : Vec2, origin : Vec2) {
+        self.transform(&(Affine2::from_translation(origin) * Affine2::from_scale(scale) * Affine2::from_translation(-origin)));
+    }
+
+    pub fn rotate(&mut self, angle : f32, origin : Vec2) {
+        self.transform(&(Affine2::from_translation(origin) * Affine2::from_angle(angle) * Affine2::from_translation(-origin)));
+    }
+
+    /// Mirrors across the vertical axis going through `origin` when `horizontal`, the horizontal one otherwise.
+    pub fn mirror(&mut self, horizontal : bool, origin : Vec2) {
+        let scale = match horizontal {
+            true => Vec2::new(-1_f32, 1_f32),
+            false => Vec2::new(1_f32, -1_f32),
+        };
+
+        self.scale(scale, origin);
+    }
+}
+
+impl<'a> IntoIterator for &'a Drawing {
+    type Item = &'a Stroke;
+    type IntoIter = std::slice::Iter<'a, Stroke>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.strokes.iter()
+    }
+}
+
+impl Extend<Stroke> for Drawing {
+    fn extend<I: IntoIterator<Item = Stroke>>(&mut self, strokes: I) {
+        self.strokes.extend(strokes);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+/// Everything that can go wrong while loading inputs or writing exports.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io { file_name : String, source : std::io::Error },
+    /// A svg or svg font file is not well formed xml, `position` being a byte offset.
+    Xml { file_name : String, position : usize, source : quick_xml::Error },
+    /// A glyph of a svg font could not be read, `position` being the byte offset of its element.
+    FontParse { file_name : String, glyph : String, position : usize, message : String },
+    /// The signature counter file does not hold a number.
+    Counter { file_name : String, content : String },
+    /// An svg export could not be written.
+    Export { file_name : String, source : std::io::Error },
+    /// A density image could not be decoded, or a png export encoded.
+    #[cfg(feature = "raster")]
+    Image { file_name : String, source : image::ImageError },
+    /// The pixels of a density map are not one byte per pixel of a non empty picture.
+    Density { width : u32, height : u32, byte_count : usize },
+    /// A config file has a line that is not `name = value`.
+    Config { file_name : String, message : String },
+    /// A command line argument is missing or does not have the expected form.
+    Argument { message : String },
+    /// The preview window could not be opened.
+    #[cfg(feature = "viewer")]
+    Viewer { source : ggez::GameError },
+}
+
+/// Result of the fallible operations of the crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(file_name: &str, source: std::io::Error) -> Error {
+        Error::Io { file_name: file_name.to_owned(), source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { file_name, source } => write!(f, "{} : {}", file_name, source),
+            Error::Xml { file_name, position, source } => write!(f, "{} at position {} : {}", file_name, position, source),
+            Error::FontParse { file_name, glyph, position, message } => write!(f, "{} glyph {} at position {} : {}", file_name, glyph, position, message),
+            Error::Counter { file_name, content } => write!(f, "{} : expected a number, found \"{}\"", file_name, content),
+            Error::Export { file_name, source } => write!(f, "could not export {} : {}", file_name, source),
+            #[cfg(feature = "raster")]
+            Error::Image { file_name, source } => write!(f, "{} : {}", file_name, source),
+            Error::Density { width, height, byte_count } => write!(f, "a density map of {} by {} pixels expects one byte per pixel, found {}", width, height, byte_count),
+            Error::Config { file_name, message } => write!(f, "{} {}", file_name, message),
+            Error::Argument { message } => write!(f, "{}", message),
+            #[cfg(feature = "viewer")]
+            Error::Viewer { source } => write!(f, "could not open the preview : {}", source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Xml { source, .. } => Some(source),
+            Error::Export { source, .. } => Some(source),
+            #[cfg(feature = "raster")]
+            Error::Image { source, .. } => Some(source),
+            #[cfg(feature = "viewer")]
+            Error::Viewer { source } => Some(source),
+            Error::FontParse { .. } | Error::Counter { .. } | Error::Density { .. } | Error::Config { .. } | Error::Argument { .. } => None,
+        }
+    }
+}
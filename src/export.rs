@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use glam::*;
+use svg::Document;
+use svg::node::element::{Group, Path};
+use svg::node::element::path::Data;
+
+use crate::drawing::*;
+use crate::error::*;
+use crate::utils::*;
+
+/// Builds a plotter ready svg document of `size` millimetres, `scale` converting drawing units into millimetres.
+///
+/// Each layer of the drawing becomes an Inkscape layer and segments already drawn are skipped, see [`exported_drawing`].
+pub fn drawing_to_svg(drawing: &Drawing, size: Vec2, scale: f32) -> Document {
+    let drawing = exported_drawing(drawing);
+    let mut document = Document::new()
+        .set("xmlns:inkscape", "http://www.inkscape.org/namespaces/inkscape")
+        .set("viewBox", (0, 0, size.x, size.y))
+        .set("width", format!("{}mm", size.x))
+        .set("height", format!("{}mm", size.y))
+        .set("units", "mm");
+
+    for layer in drawing.layers() {
+        let mut group = Group::new()
+            .set("id", layer.clone())
+            .set("inkscape:label", layer.clone())
+            .set("inkscape:groupmode", "layer");
+
+        // Consecutive strokes sharing the same pen are merged in a single path.
+        let mut data = Data::new();
+        let mut pen : Option<&StrokeAttributes> = None;
+        for stroke in drawing.layer(&layer) {
+            if let Some(attributes) = pen {
+                if attributes.stroke_width != stroke.attributes.stroke_width || attributes.color != stroke.attributes.color {
+                    group = group.add(svg_path(data, attributes));
+                    data = Data::new();
+                }
+            }
+
+            pen = Some(&stroke.attributes);
+            for instruction in stroke.instructions() {
+                data = instruction.execute_instruction(data, scale);
+            }
+
+            if stroke.attributes.closed {
+                data = data.close();
+            }
+        }
+
+        if let Some(attributes) = pen {
+            group = group.add(svg_path(data, attributes));
+        }
+
+        document = document.add(group);
+    }
+
+    document
+}
+
+/// The strokes in plot order as the export draws them, lines already drawn by an earlier stroke being left out.
+///
+/// A stroke losing some of its lines is split around them, and no longer closed.
+pub fn exported_drawing(drawing: &Drawing) -> Drawing {
+    let mut drawn_points : HashSet<OrderedPair> = HashSet::new();
+    let mut exported = Drawing::new();
+    for stroke in drawing.plot_order() {
+        let open = StrokeAttributes { closed: false, ..stroke.attributes.clone() };
+        let mut pieces : Vec<Stroke> = Vec::new();
+        let mut piece : Option<Stroke> = None;
+        let mut is_split = false;
+        let mut position = stroke.start();
+        for instruction in &stroke.instructions()[1..] {
+            let is_drawn = match instruction {
+                Instruction::LineTo(to) => !drawn_points.insert(OrderedPair::new(position, *to)),
+                _ => false,
+            };
+
+            match is_drawn {
+                true => {
+                    pieces.extend(piece.take());
+                    is_split = true;
+                },
+                false => {
+                    let piece = piece.get_or_insert_with(|| Stroke::new(position, open.clone()));
+                    match *instruction {
+                        Instruction::LineTo(to) => piece.line_to(to),
+                        Instruction::QuadTo(control, to) => piece.quad_to(control, to),
+                        Instruction::CubicTo(control1, control2, to) => piece.cubic_to(control1, control2, to),
+                        Instruction::MoveTo(_) => (),
+                    }
+                },
+            }
+
+            position = instruction.end_point();
+        }
+
+        if stroke.attributes.closed {
+            let start = stroke.start();
+            let is_new = position != start && drawn_points.insert(OrderedPair::new(position, start));
+            match (is_split, piece.as_mut()) {
+                // Nothing was left out, the stroke stays closed.
+                (false, Some(whole)) => whole.attributes.closed = true,
+                _ if is_new => piece.get_or_insert_with(|| Stroke::new(position, open.clone())).line_to(start),
+                _ => (),
+            }
+        }
+
+        pieces.extend(piece);
+        exported.extend(pieces);
+    }
+
+    exported
+}
+
+/// Writes the drawing as an svg file, see [`drawing_to_svg`].
+pub fn save_svg(drawing: &Drawing, size: Vec2, scale: f32, file_name: &str) -> Result<()> {
+    svg::save(file_name, &drawing_to_svg(drawing, size, scale)).map_err(|source| Error::Export { file_name: file_name.to_owned(), source })
+}
+
+fn svg_path(data: Data, attributes: &StrokeAttributes) -> Path {
+    Path::new()
+        .set("fill", "none")
+        .set("stroke", attributes.color.clone())
+        .set("stroke-width", attributes.stroke_width)
+        .set("d", data)
+}
+
+/// Renders the drawing as black one pixel lines on white, `pixels_per_millimetre` setting the resolution.
+#[cfg(feature = "raster")]
+pub fn save_png(drawing: &Drawing, size: Vec2, scale: f32, pixels_per_millimetre: f32, file_name: &str) -> Result<()> {
+    let width = (size.x * pixels_per_millimetre).ceil().max(1_f32) as u32;
+    let height = (size.y * pixels_per_millimetre).ceil().max(1_f32) as u32;
+    let mut image = image::GrayImage::from_pixel(width, height, image::Luma([255]));
+    let to_pixels = pixels_per_millimetre / scale;
+
+    for stroke in drawing {
+        for pair in stroke.flattened_points(8).windows(2) {
+            let from = pair[0] * to_pixels;
+            let to = pair[1] * to_pixels;
+            // Two samples per pixel so rounding never leaves a gap.
+            let steps = (from.distance(to) * 2_f32).ceil().max(1_f32) as usize;
+            for step in 0..=steps {
+                let pixel = from.lerp(to, step as f32 / steps as f32);
+                if pixel.x >= 0_f32 && pixel.y >= 0_f32 && (pixel.x as u32) < width && (pixel.y as u32) < height {
+                    image.put_pixel(pixel.x as u32, pixel.y as u32, image::Luma([0]));
+                }
+            }
+        }
+    }
+
+    image.save(file_name).map_err(|source| Error::Image { file_name: file_name.to_owned(), source })
+}
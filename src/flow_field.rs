@@ -0,0 +1,169 @@
+use std::collections::{HashMap, VecDeque};
+
+use glam::*;
+
+use crate::drawing::*;
+use crate::noise::*;
+use crate::utils::*;
+
+#[derive(Copy, Clone)]
+pub struct FlowFieldParameters {
+    /// Noise frequency per drawing unit.
+    pub noise_scale : f32,
+    pub octaves : usize,
+    /// Distance kept between two streamlines.
+    pub separation : f32,
+    /// Fraction of the separation under which a growing streamline stops.
+    pub test_ratio : f32,
+    /// Integration step along the field.
+    pub step : f32,
+    pub min_points : usize,
+    pub max_points : usize,
+}
+
+// Buckets of streamline points, a cell being as large as the separation.
+struct PointGrid {
+    cell_size : f32,
+    cells : HashMap<(i32, i32), Vec<Vec2>>,
+}
+
+impl PointGrid {
+    fn new(cell_size: f32) -> PointGrid {
+        PointGrid { cell_size, cells: HashMap::new() }
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        ((position.x / self.cell_size).floor() as i32, (position.y / self.cell_size).floor() as i32)
+    }
+
+    fn insert(&mut self, position: Vec2) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(position);
+    }
+
+    fn is_free(&self, position: Vec2, distance: f32) -> bool {
+        let (x, y) = self.cell(position);
+        let reach = (distance / self.cell_size).ceil() as i32;
+        for cx in (x - reach)..(x + reach + 1) {
+            for cy in (y - reach)..(y + reach + 1) {
+                if let Some(points) = self.cells.get(&(cx, cy)) {
+                    if points.iter().any(|point| point.distance_squared(position) < distance * distance) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+pub struct FlowField {
+    noise : Perlin,
+    parameters : FlowFieldParameters,
+}
+
+impl FlowField {
+    pub fn new(noise: Perlin, parameters: FlowFieldParameters) -> FlowField {
+        FlowField { noise, parameters }
+    }
+
+    pub fn direction(&self, position: Vec2) -> Vec2 {
+        let angle = self.noise.fractal(position * self.parameters.noise_scale, self.parameters.octaves) * std::f32::consts::TAU;
+        Vec2::new(angle.cos(), angle.sin())
+    }
+
+    // Midpoint integration, `sign` choosing to go with or against the field.
+    fn advance(&self, position: Vec2, sign: f32) -> Vec2 {
+        let half = position + self.direction(position) * sign * self.parameters.step / 2_f32;
+        position + self.direction(half) * sign * self.parameters.step
+    }
+
+    fn trace<F>(&self, seed: Vec2, points: &PointGrid, inside: &F) -> Vec<Vec2> where F: Fn(Vec2) -> bool {
+        let test_distance = self.parameters.separation * self.parameters.test_ratio;
+        let mut halves = Vec::new();
+        for sign in [1_f32, -1_f32] {
+            let mut own = PointGrid::new(self.parameters.separation);
+            let mut line = Vec::new();
+            let mut position = seed;
+            while line.len() < self.parameters.max_points / 2 {
+                let next = self.advance(position, sign);
+                // Stalled on a sink, stuck against another streamline or about to loop on itself.
+                if next.distance_squared(position) < f32::EPSILON || !inside(next) || !points.is_free(next, test_distance) || !own.is_free(next, test_distance) {
+                    break;
+                }
+
+                // Its own last points are always close, they only count once far enough behind.
+                if line.len() > 2 {
+                    own.insert(line[line.len() - 3]);
+                }
+
+                line.push(next);
+                position = next;
+            }
+
+            halves.push(line);
+        }
+
+        let forward = halves.remove(0);
+        let mut streamline : Vec<Vec2> = halves.remove(0).into_iter().rev().collect();
+        streamline.push(seed);
+        streamline.extend(forward);
+        streamline
+    }
+
+    /// Evenly-spaced streamlines as described by Jobard and Lefer, seeded from the neighborhood of the accepted ones.
+    pub fn streamlines<F>(&self, bounds: &Bounds, inside: F) -> Vec<Vec<Vec2>> where F: Fn(Vec2) -> bool {
+        let inside = |position: Vec2| bounds.contains(position) && inside(position);
+        let mut points = PointGrid::new(self.parameters.separation);
+        let mut streamlines = Vec::new();
+        let mut seeds = VecDeque::new();
+
+        // Coarse lattice of fallback seeds so disconnected regions get lines too.
+        let mut y = bounds.min.y + self.parameters.separation / 2_f32;
+        while y < bounds.max.y {
+            let mut x = bounds.min.x + self.parameters.separation / 2_f32;
+            while x < bounds.max.x {
+                seeds.push_back(Vec2::new(x, y));
+                x += self.parameters.separation * 4_f32;
+            }
+
+            y += self.parameters.separation * 4_f32;
+        }
+
+        seeds.push_front(bounds.center());
+
+        while let Some(seed) = seeds.pop_front() {
+            // Seeds are placed exactly one separation away from their streamline.
+            if !inside(seed) || !points.is_free(seed, self.parameters.separation * 0.99_f32) {
+                continue;
+            }
+
+            let streamline = self.trace(seed, &points, &inside);
+            if streamline.len() < self.parameters.min_points {
+                continue;
+            }
+
+            for (index, point) in streamline.iter().enumerate() {
+                points.insert(*point);
+                if index + 1 < streamline.len() {
+                    let normal = (streamline[index + 1] - *point).normalize_or_zero().perp() * self.parameters.separation;
+                    seeds.push_front(*point - normal);
+                    seeds.push_front(*point + normal);
+                }
+            }
+
+            streamlines.push(streamline);
+        }
+
+        streamlines
+    }
+
+    pub fn generate<F>(&self, bounds: &Bounds, inside: F, smoothing: SmoothingParameters, attributes: &StrokeAttributes, drawing: &mut Drawing) where F: Fn(Vec2) -> bool {
+        for streamline in self.streamlines(bounds, inside) {
+            let mut instructions = Vec::new();
+            smooth_into_instructions(streamline, smoothing.smoothing_mode, smoothing.smooth_number_of_points, smoothing.smooth_sharpness, &mut instructions);
+            drawing.add_instructions(&instructions, attributes);
+        }
+    }
+}
@@ -7,24 +7,28 @@ use quick_xml::Reader;
 use quick_xml::events::Event;
 
 use crate::utils::*;
+use crate::drawing::*;
+use crate::error::*;
 
 pub struct Sigil {
     path : Vec<Instruction>,
     width : f32, 
 }
 
+/// Single line font read from a svg font, glyphs being kept as pen instructions.
 pub struct Font {
     sigils: HashMap<String, Sigil>,
 } 
 
 impl Font {
 
-    pub fn load(font_name: &str) -> Font {
+    /// Reads the glyphs of the supported characters from a svg font file.
+    pub fn load(font_name: &str) -> Result<Font> {
         
-        let accepted_characters = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ?.-#*".to_owned();
+        let accepted_characters = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ?.-#*_=:,".to_owned();
 
         let font_file_raw = fs::read_to_string(font_name)
-            .expect("Something went wrong reading the font file");
+            .map_err(|error| Error::io(font_name, error))?;
         let mut reader = Reader::from_str(&font_file_raw);
 
         let mut font = Font{
@@ -32,31 +36,34 @@ impl Font {
         };
         let mut font_scale = 1_f32;
         loop {
+            let position = reader.buffer_position();
+            let parse_error = |glyph: &str, message: String| Error::FontParse { file_name: font_name.to_owned(), glyph: glyph.to_owned(), position, message };
             match reader.read_event_unbuffered() {
                 Ok(Event::Empty(ref e)) => {
                     match e.name() {
                         b"font-face" => {
                             for attr in e.attributes() {
-                                let attr = attr.unwrap();
-                                let attr_value = from_utf8(&attr.value).unwrap().to_owned();
-                                match attr.key {
-                                    b"units-per-em" => {
-                                        font_scale = attr_value.parse::<f32>().unwrap();
-                                    },
-                                    _ => (),
+                                let attr = attr.map_err(|error| parse_error("font-face", format!("{}", error)))?;
+                                let attr_value = from_utf8(&attr.value).map_err(|error| parse_error("font-face", format!("{}", error)))?.to_owned();
+                                if attr.key == b"units-per-em" {
+                                    font_scale = attr_value.parse::<f32>().map_err(|_| parse_error("font-face", format!("units-per-em expects a number, found {}", attr_value)))?;
                                 }
                             }
                         },
                         b"glyph" => {
+                            let mut name = String::from("glyph");
                             let mut unicode = Option::None;
                             let mut width= Option::None;
                             let mut path= Option::None;
 
                             for attr in e.attributes() {
-                                let attr = attr.unwrap();
+                                let attr = attr.map_err(|error| parse_error(&name, format!("{}", error)))?;
 
-                                let attr_value = from_utf8(&attr.value).unwrap().to_owned();
+                                let attr_value = from_utf8(&attr.value).map_err(|error| parse_error(&name, format!("{}", error)))?.to_owned();
                                 match attr.key {
+                                    b"glyph-name" => {
+                                        name = attr_value;
+                                    },
                                     b"unicode" =>{
                                         unicode = Some(attr_value);
                                     },
@@ -70,41 +77,40 @@ impl Font {
                                 }
                             }
 
-                            match (unicode, width, path) {
-                                (Some(unicode), Some(width), Some(path)) => {
-                                    if accepted_characters.contains(&unicode)
-                                    {
-                                        let mut instructions = Vec::new();
-
-                                        let splitted : Vec<&str> = path.split(" ").collect();
-                                        
-                                        for split in splitted.chunks(3) {
-                                            let operation = split[0];
-                                            let x = split[1].parse::<f32>().unwrap() / font_scale;
-                                            let y = split[2].parse::<f32>().unwrap() * -1_f32 / font_scale;
-                                            let pos = Vec2::new(x, y);
-                                            instructions.push(match operation {
-                                                "M" => {Instruction::MoveTo(pos)},
-                                                "L" => {Instruction::LineTo(pos)},
-                                                _=> panic!("Unknown Instruction {}.", operation)
-                                            });
-                                        }
-
-                                        font.sigils.insert(unicode,
-                                            Sigil {
-                                                path : instructions,
-                                                width: width.parse::<f32>().unwrap() / font_scale,
-                                                });
+                            if let (Some(unicode), Some(width), Some(path)) = (unicode, width, path) {
+                                if accepted_characters.contains(&unicode) {
+                                    let mut instructions = Vec::new();
+
+                                    let splitted : Vec<&str> = path.split(" ").collect();
+
+                                    for split in splitted.chunks(3) {
+                                        let coordinate = |index: usize| split.get(index).and_then(|value| value.parse::<f32>().ok())
+                                            .ok_or_else(|| parse_error(&name, format!("{} expects two coordinates", split[0])));
+                                        let operation = split[0];
+                                        let x = coordinate(1)? / font_scale;
+                                        let y = -coordinate(2)? / font_scale;
+                                        let pos = Vec2::new(x, y);
+                                        instructions.push(match operation {
+                                            "M" => {Instruction::MoveTo(pos)},
+                                            "L" => {Instruction::LineTo(pos)},
+                                            _=> return Err(parse_error(&name, format!("unknown instruction {}", operation))),
+                                        });
                                     }
-                                },
-                                _ => (),
+
+                                    let width = width.parse::<f32>().map_err(|_| parse_error(&name, format!("horiz-adv-x expects a number, found {}", width)))?;
+                                    font.sigils.insert(unicode,
+                                        Sigil {
+                                            path : instructions,
+                                            width: width / font_scale,
+                                            });
+                                }
                             }
                         },
                         _ => (),
                     }
                 },
             Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Err(source) => return Err(Error::Xml { file_name: font_name.to_owned(), position: reader.buffer_position(), source }),
             _ => (),
             }
         }
@@ -113,46 +119,42 @@ impl Font {
             Sigil { path: Vec::new(), width: 300_f32 / font_scale},
         );
 
-        return font;
+        Ok(font)
     }
 
+    /// Width of the text once printed at `scale`, unknown characters taking no room.
     pub fn get_width(&self, data: String, scale: f32) -> f32 {
-        let mut width = 0_f32;
-        for (_, char) in data.chars().enumerate(){
-            let sigil = self.sigils.get(&char.to_string());
-            match sigil {
-                Some(sigil) => {
-                    width = width + sigil.width;
-                },
-                None => { println!("Fond does not contains {}", char)}
-            }
-        }
-    
-        return width * scale;
+        let width : f32 = data.chars().filter_map(|char| self.sigils.get(&char.to_string())).map(|sigil| sigil.width).sum();
+        width * scale
     }
 
-    pub fn print_in_instructions(&self, data : String, position : Vec2, scale : f32, instructions : &mut Vec<Instruction>) {
+    /// Lays out the text on a single line, `position` being the baseline start.
+    pub fn print_in_drawing(&self, data : String, position : Vec2, scale : f32, attributes : &StrokeAttributes, drawing : &mut Drawing) {
         let mut current_position = position;
-        
-        for (_, char) in data.chars().enumerate(){
+        let mut instructions = Vec::new();
+        let mut missing = String::new();
+
+        for char in data.chars() {
             let sigil = self.sigils.get(&char.to_string());
             match sigil {
                 Some(sigil) => {
                     for instruction in &sigil.path {
-                        match instruction {
-                            Instruction::MoveTo(pos) => {
-                                instructions.push(Instruction::MoveTo(pos.to_owned() * scale + current_position));
-                            },
-                            Instruction::LineTo(pos) => {
-                                instructions.push(Instruction::LineTo(pos.to_owned() * scale + current_position));
-                            }
-                        }
+                        instructions.push(instruction.map(|pos| pos * scale + current_position));
                     }
 
-                    current_position.x = current_position.x + sigil.width * scale;
+                    current_position.x += sigil.width * scale;
+                },
+                None => if !missing.contains(char) {
+                    missing.push(char);
                 },
-                None => { println!("Fond does not contains {}", char)}
             }
         }
+
+        // Reported once for the whole text, the missing characters being skipped.
+        if !missing.is_empty() {
+            println!("Font does not contain {}", missing);
+        }
+
+        drawing.add_instructions(&instructions, attributes);
     }
 }
\ No newline at end of file
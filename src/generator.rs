@@ -0,0 +1,423 @@
+use std::collections::HashSet;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::drawing::*;
+use crate::flow_field::*;
+use crate::grid::*;
+use crate::import::*;
+use crate::noise::*;
+use crate::truchet::*;
+use crate::utils::*;
+
+/// Every parameter is exposed as a number so it can be edited, swept or read from text the same way.
+#[derive(Copy, Clone, Debug)]
+pub struct Parameter {
+    pub name : &'static str,
+    pub value : f32,
+    pub min : f32,
+    pub max : f32,
+    pub integer : bool,
+}
+
+impl Parameter {
+    pub fn float(name: &'static str, value: f32, min: f32, max: f32) -> Parameter {
+        Parameter { name, value, min, max, integer: false }
+    }
+
+    pub fn integer(name: &'static str, value: usize, min: usize, max: usize) -> Parameter {
+        Parameter { name, value: value as f32, min: min as f32, max: max as f32, integer: true }
+    }
+}
+
+/// What a generator can draw on, coordinates being in preview pixels.
+pub struct GeneratorContext<'a> {
+    pub grid : &'a Grid,
+    pub bounds : Bounds,
+    /// Preview pixels per millimetre.
+    pub scale : f32,
+    /// Closed shapes the content must stay in.
+    pub mask : Option<&'a Drawing>,
+    pub constraints : &'a TileConstraints,
+}
+
+/// What a generator keeps of its last generation to regenerate regions of it, saved along the history.
+#[derive(Clone, Default)]
+pub enum GeneratorCache {
+    #[default]
+    Empty,
+    /// Tiles of every walk, and the tiles they went through.
+    Walks { walks : Vec<Vec<usize>>, visited_tiles : Vec<usize> },
+    /// Rotation of every tile, None for the empty ones, and the tiles drawn in.
+    Rotations { rotations : Vec<Option<usize>>, visited_tiles : Vec<usize> },
+}
+
+/// Something that fills a drawing from a seed, driven by named numeric parameters.
+pub trait Generator {
+    fn name(&self) -> &'static str;
+
+    fn parameters(&self) -> Vec<Parameter>;
+
+    /// Returns false when the generator has no such parameter.
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool;
+
+    fn generate(&mut self, seed: u64, context: &GeneratorContext, drawing: &mut Drawing);
+
+    /// Generates again only what goes through the given tiles, keeping the rest of the last generation.
+    /// Returns false when the generator does not support it, leaving the drawing untouched.
+    fn generate_region(&mut self, _seed: u64, _context: &GeneratorContext, _region: &[usize], _drawing: &mut Drawing) -> bool {
+        false
+    }
+
+    /// What generate_region works from, to be given back when an older drawing is displayed again.
+    fn cache(&self) -> GeneratorCache {
+        GeneratorCache::Empty
+    }
+
+    fn restore_cache(&mut self, _cache: GeneratorCache) {}
+
+    /// Tiles the last generation went through, for generators working on the grid.
+    fn visited_tiles(&self) -> &[usize] {
+        &[]
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters().iter().find(|parameter| parameter.name == name).map(|parameter| parameter.value)
+    }
+}
+
+impl SmoothingMode {
+    pub fn from_index(index: usize) -> SmoothingMode {
+        match index {
+            1 => SmoothingMode::Chaikin,
+            2 => SmoothingMode::CatmullRom,
+            3 => SmoothingMode::QuadraticCurve,
+            4 => SmoothingMode::CubicBezier,
+            _ => SmoothingMode::Quadratic,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            SmoothingMode::Quadratic => 0,
+            SmoothingMode::Chaikin => 1,
+            SmoothingMode::CatmullRom => 2,
+            SmoothingMode::QuadraticCurve => 3,
+            SmoothingMode::CubicBezier => 4,
+        }
+    }
+}
+
+fn smoothing_parameters(parameters: &SmoothingParameters) -> Vec<Parameter> {
+    vec![
+        Parameter::integer("smooth_number_of_points", parameters.smooth_number_of_points, 1, 16),
+        Parameter::float("smooth_sharpness", parameters.smooth_sharpness, 0_f32, 1_f32),
+        Parameter::integer("smoothing_mode", parameters.smoothing_mode.index(), 0, 4),
+    ]
+}
+
+fn set_smoothing_parameter(parameters: &mut SmoothingParameters, name: &str, value: f32) -> bool {
+    match name {
+        "smooth_number_of_points" => parameters.smooth_number_of_points = value.round().max(1_f32) as usize,
+        "smooth_sharpness" => parameters.smooth_sharpness = value,
+        "smoothing_mode" => parameters.smoothing_mode = SmoothingMode::from_index(value.round().max(0_f32) as usize),
+        _ => return false,
+    }
+
+    true
+}
+
+/// Smoothed random walks over the hex grid.
+pub struct HexWalkGenerator {
+    pub parameters : RandomWalkParameters,
+    walks : Vec<Vec<usize>>,
+    visited_tiles : Vec<usize>,
+}
+
+impl HexWalkGenerator {
+    pub fn new(parameters: RandomWalkParameters) -> HexWalkGenerator {
+        HexWalkGenerator { parameters, walks: Vec::new(), visited_tiles: Vec::new() }
+    }
+}
+
+impl Generator for HexWalkGenerator {
+    fn name(&self) -> &'static str {
+        "hex_walk"
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        let mut parameters = vec![Parameter::float("slice_percentage", self.parameters.slice_percentage, 0_f32, 1_f32)];
+        parameters.extend(smoothing_parameters(&self.parameters.smoothing));
+        parameters.push(Parameter::float("density_bias", self.parameters.density_bias, 0_f32, 8_f32));
+        parameters.push(Parameter::integer("density_revisits", self.parameters.density_revisits, 0, 8));
+        parameters.push(Parameter::float("density_sharpness", self.parameters.density_sharpness, 0_f32, 1_f32));
+        parameters
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "slice_percentage" => self.parameters.slice_percentage = value.clamp(0_f32, 1_f32),
+            "density_bias" => self.parameters.density_bias = value,
+            "density_revisits" => self.parameters.density_revisits = value.round().max(0_f32) as usize,
+            "density_sharpness" => self.parameters.density_sharpness = value,
+            _ => return set_smoothing_parameter(&mut self.parameters.smoothing, name, value),
+        }
+
+        true
+    }
+
+    fn generate(&mut self, seed: u64, context: &GeneratorContext, drawing: &mut Drawing) {
+        let mut random = StdRng::seed_from_u64(seed);
+        self.walks = context.grid.random_walk(&mut random, self.parameters, context.constraints);
+        self.visited_tiles = context.grid.draw_walks(&self.walks, self.parameters, &StrokeAttributes::new("walk"), drawing);
+    }
+
+    // Walks touching the region are dropped and their tiles walked again, along with the region itself.
+    fn generate_region(&mut self, seed: u64, context: &GeneratorContext, region: &[usize], drawing: &mut Drawing) -> bool {
+        let (removed, mut walks) : (Vec<Vec<usize>>, Vec<Vec<usize>>) = std::mem::take(&mut self.walks).into_iter().partition(|walk| walk.iter().any(|index| region.contains(index)));
+        let freed : HashSet<usize> = removed.into_iter().flatten().chain(region.iter().copied()).collect();
+
+        let mut constraints = context.constraints.clone();
+        constraints.excluded.extend((0..context.grid.tiles.len()).filter(|index| !freed.contains(index)));
+        constraints.starts.retain(|start| freed.contains(start));
+
+        let mut random = StdRng::seed_from_u64(seed);
+        walks.extend(context.grid.random_walk(&mut random, self.parameters, &constraints));
+        self.walks = walks;
+        self.visited_tiles = context.grid.draw_walks(&self.walks, self.parameters, &StrokeAttributes::new("walk"), drawing);
+        true
+    }
+
+    fn visited_tiles(&self) -> &[usize] {
+        &self.visited_tiles
+    }
+
+    fn cache(&self) -> GeneratorCache {
+        GeneratorCache::Walks { walks: self.walks.clone(), visited_tiles: self.visited_tiles.clone() }
+    }
+
+    fn restore_cache(&mut self, cache: GeneratorCache) {
+        (self.walks, self.visited_tiles) = match cache {
+            GeneratorCache::Walks { walks, visited_tiles } => (walks, visited_tiles),
+            _ => (Vec::new(), Vec::new()),
+        };
+    }
+}
+
+/// Streamlines following a noise flow field.
+pub struct FlowFieldGenerator {
+    /// Distances are in millimetres.
+    pub parameters : FlowFieldParameters,
+    pub smoothing : SmoothingParameters,
+}
+
+impl Generator for FlowFieldGenerator {
+    fn name(&self) -> &'static str {
+        "flow_field"
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        let mut parameters = vec![
+            Parameter::float("noise_scale", self.parameters.noise_scale, 0.001_f32, 0.2_f32),
+            Parameter::integer("octaves", self.parameters.octaves, 1, 6),
+            Parameter::float("separation", self.parameters.separation, 0.5_f32, 10_f32),
+            Parameter::float("test_ratio", self.parameters.test_ratio, 0.1_f32, 1_f32),
+            Parameter::float("step", self.parameters.step, 0.1_f32, 5_f32),
+            Parameter::integer("min_points", self.parameters.min_points, 2, 200),
+            Parameter::integer("max_points", self.parameters.max_points, 10, 10000),
+        ];
+
+        parameters.extend(smoothing_parameters(&self.smoothing));
+        parameters
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "noise_scale" => self.parameters.noise_scale = value,
+            "octaves" => self.parameters.octaves = value.round().max(1_f32) as usize,
+            "separation" => self.parameters.separation = value.max(0.01_f32),
+            "test_ratio" => self.parameters.test_ratio = value,
+            "step" => self.parameters.step = value.max(0.01_f32),
+            "min_points" => self.parameters.min_points = value.round().max(2_f32) as usize,
+            "max_points" => self.parameters.max_points = value.round().max(2_f32) as usize,
+            _ => return set_smoothing_parameter(&mut self.smoothing, name, value),
+        }
+
+        true
+    }
+
+    fn generate(&mut self, seed: u64, context: &GeneratorContext, drawing: &mut Drawing) {
+        let mut random = StdRng::seed_from_u64(seed);
+        let mut parameters = self.parameters;
+        parameters.separation *= context.scale;
+        parameters.step *= context.scale;
+        parameters.noise_scale /= context.scale;
+
+        let flow_field = FlowField::new(Perlin::new(&mut random), parameters);
+        let mask = context.mask;
+        flow_field.generate(&context.bounds, |position| mask.map_or(true, |mask| is_inside(mask, position)), self.smoothing, &StrokeAttributes::new("flow"), drawing);
+    }
+}
+
+/// Truchet tiles, arcs turned at random in each tile of the grid and joined across tiles into long curves.
+pub struct TruchetGenerator {
+    /// Chance for a tile to turn its motif by one side, 0.5 for an even mix.
+    pub rotation_bias : f32,
+    rotations : Vec<Option<usize>>,
+    visited_tiles : Vec<usize>,
+}
+
+impl TruchetGenerator {
+    pub fn new(rotation_bias: f32) -> TruchetGenerator {
+        TruchetGenerator { rotation_bias, rotations: Vec::new(), visited_tiles: Vec::new() }
+    }
+
+    // None for the tiles excluded by hand or outside of the mask.
+    fn rotation<R: Rng>(&self, random: &mut R, tile: &TileInfo, context: &GeneratorContext) -> Option<usize> {
+        let sides = context.grid.tile_sides(tile.index).len().max(1);
+        // Drawn first so the rotation of a tile does not depend on which tiles are left empty.
+        let rotation = (2 * random.gen_range(0..sides.div_ceil(2)) + (random.gen::<f32>() < self.rotation_bias) as usize) % sides;
+        let included = !context.constraints.excluded.contains(&tile.index) && context.mask.map_or(true, |mask| is_inside(mask, tile.position));
+        included.then_some(rotation)
+    }
+
+    fn draw(&mut self, context: &GeneratorContext, drawing: &mut Drawing) {
+        self.visited_tiles = (0..self.rotations.len()).filter(|index| self.rotations[*index].is_some()).collect();
+        for instructions in truchet_curves(context.grid, &self.rotations) {
+            drawing.add_instructions(&instructions, &StrokeAttributes::new("truchet"));
+        }
+    }
+}
+
+impl Generator for TruchetGenerator {
+    fn name(&self) -> &'static str {
+        "truchet"
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![Parameter::float("rotation_bias", self.rotation_bias, 0_f32, 1_f32)]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rotation_bias" => self.rotation_bias = value.clamp(0_f32, 1_f32),
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn generate(&mut self, seed: u64, context: &GeneratorContext, drawing: &mut Drawing) {
+        let mut random = StdRng::seed_from_u64(seed);
+        self.rotations = context.grid.tiles.iter().map(|tile| self.rotation(&mut random, tile, context)).collect();
+        self.draw(context, drawing);
+    }
+
+    // Only the tiles of the region are turned again.
+    fn generate_region(&mut self, seed: u64, context: &GeneratorContext, region: &[usize], drawing: &mut Drawing) -> bool {
+        if self.rotations.len() != context.grid.tiles.len() {
+            return false;
+        }
+
+        let mut random = StdRng::seed_from_u64(seed);
+        for index in region {
+            self.rotations[*index] = self.rotation(&mut random, &context.grid.tiles[*index], context);
+        }
+
+        self.draw(context, drawing);
+        true
+    }
+
+    fn visited_tiles(&self) -> &[usize] {
+        &self.visited_tiles
+    }
+
+    fn cache(&self) -> GeneratorCache {
+        GeneratorCache::Rotations { rotations: self.rotations.clone(), visited_tiles: self.visited_tiles.clone() }
+    }
+
+    fn restore_cache(&mut self, cache: GeneratorCache) {
+        (self.rotations, self.visited_tiles) = match cache {
+            GeneratorCache::Rotations { rotations, visited_tiles } => (rotations, visited_tiles),
+            _ => (Vec::new(), Vec::new()),
+        };
+    }
+}
+
+type GeneratorFactory = fn() -> Box<dyn Generator>;
+
+/// Generators available by name.
+pub struct GeneratorRegistry {
+    factories : Vec<(&'static str, GeneratorFactory)>,
+}
+
+impl GeneratorRegistry {
+    /// Registry without any generator, [`GeneratorRegistry::with_defaults`] holding the ones of the crate.
+    pub fn empty() -> GeneratorRegistry {
+        GeneratorRegistry { factories: Vec::new() }
+    }
+
+    pub fn with_defaults() -> GeneratorRegistry {
+        let mut registry = GeneratorRegistry::empty();
+        registry.register("hex_walk", || Box::new(HexWalkGenerator::new(RandomWalkParameters {
+            slice_percentage: 0.5_f32,
+            smoothing: SmoothingParameters {
+                smooth_number_of_points: 4,
+                smooth_sharpness: 0.9_f32,
+                smoothing_mode: SmoothingMode::Quadratic,
+            },
+            density_bias: 0_f32,
+            density_revisits: 0,
+            density_sharpness: 0_f32,
+        })));
+
+        registry.register("flow_field", || Box::new(FlowFieldGenerator {
+            parameters: FlowFieldParameters {
+                noise_scale: 0.02_f32,
+                octaves: 2,
+                separation: 2_f32,
+                test_ratio: 0.5_f32,
+                step: 0.5_f32,
+                min_points: 10,
+                max_points: 2000,
+            },
+            smoothing: SmoothingParameters {
+                smooth_number_of_points: 4,
+                smooth_sharpness: 0.9_f32,
+                smoothing_mode: SmoothingMode::Quadratic,
+            },
+        }));
+
+        registry.register("truchet", || Box::new(TruchetGenerator::new(0.5_f32)));
+
+        registry
+    }
+
+    /// A generator registered twice under the same name replaces the previous one.
+    pub fn register(&mut self, name: &'static str, factory: GeneratorFactory) {
+        self.factories.retain(|(other, _)| *other != name);
+        self.factories.push((name, factory));
+    }
+
+    pub fn create(&self, name: &str) -> Option<Box<dyn Generator>> {
+        self.factories.iter().find(|(other, _)| *other == name).map(|(_, factory)| factory())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.factories.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// The name following `current` in registration order, wrapping around.
+    pub fn next_name(&self, current: &str) -> &'static str {
+        let names = self.names();
+        let index = names.iter().position(|name| *name == current).map_or(0, |index| (index + 1) % names.len());
+        names[index]
+    }
+}
+
+impl Default for GeneratorRegistry {
+    fn default() -> GeneratorRegistry {
+        GeneratorRegistry::with_defaults()
+    }
+}
@@ -1,16 +1,29 @@
 use glam::*;
 use rand::Rng;
+use rand::distributions::{Distribution, WeightedIndex};
 
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::utils::*;
+use crate::drawing::*;
+use crate::hex::*;
 
 pub struct TileInfo {
     pub index : usize,
     pub position : Vec2,
     pub neighbors : Vec<usize>,
     pub vertices : Vec<Vec2>,
+    /// Darkness sampled from a density map, 1 when the grid is not driven by an image.
+    pub density : f32,
+    /// Axial coordinates of hexagonal tiles.
+    pub hex : Option<Hex>,
+    /// Times the tile was split from a tile of the original grid.
+    pub level : usize,
+    /// Ids of the corners in `Grid::vertices`, in the order of `vertices`.
+    pub vertex_ids : Vec<usize>,
+    /// Ids of the sides in `Grid::edges`, from each corner to the next.
+    pub edges : Vec<usize>,
 }
 
 impl TileInfo {
@@ -20,20 +33,109 @@ impl TileInfo {
             position,
             neighbors : Vec::new(),
             vertices : Vec::new(),
+            density : 1_f32,
+            hex : None,
+            level : 0,
+            vertex_ids : Vec::new(),
+            edges : Vec::new(),
         }
     }
 }
 
+/// Side between two corners of the grid, `left` and `right` being the tiles on each side going from `from` to `to` on screen.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GridEdge {
+    pub from : usize,
+    pub to : usize,
+    pub left : Option<usize>,
+    pub right : Option<usize>,
+}
+
+impl GridEdge {
+    /// The tile across the edge from `tile`, None on the border of the grid.
+    pub fn other_tile(&self, tile: usize) -> Option<usize> {
+        match self.left == Some(tile) {
+            true => self.right,
+            false => self.left,
+        }
+    }
+}
+
+/// Tiles with their neighbors, walks being lists of tile indexes.
 pub struct Grid {
     pub tiles : Vec<TileInfo>,
+    /// Corners shared by the tiles, each position only once.
+    pub vertices : Vec<Vec2>,
+    /// Sides shared by the tiles, each only once.
+    pub edges : Vec<GridEdge>,
+    // Index of the tile at each axial coordinate.
+    hex_tiles : HashMap<Hex, usize>,
     pub tile_scale : f32,
 }
 
+/// Edits made by hand on the tiles, indexes being the ones of the grid they were made on.
+#[derive(Clone, Default, Debug)]
+pub struct TileConstraints {
+    /// Tiles no walk goes through.
+    pub excluded : HashSet<usize>,
+    /// Tiles walks start from, in order, before any random start.
+    pub starts : Vec<usize>,
+}
+
+impl TileConstraints {
+    pub fn toggle_excluded(&mut self, index: usize) {
+        if !self.excluded.remove(&index) {
+            self.excluded.insert(index);
+            self.starts.retain(|start| *start != index);
+        }
+    }
+
+    pub fn toggle_start(&mut self, index: usize) {
+        match self.starts.iter().position(|start| *start == index) {
+            Some(position) => { self.starts.remove(position); },
+            None => {
+                self.starts.push(index);
+                self.excluded.remove(&index);
+            },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.excluded.is_empty() && self.starts.is_empty()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GridShape {
+    Rectangle,
+    /// The largest hexagon fitting in `col` by `row` tiles.
+    Hexagon,
+    /// `col` by `row` square tiles instead of hexagons.
+    Square,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GridParameters {
+    pub col : usize,
+    pub row : usize,
+    pub tile_scale : f32,
+    pub shape : GridShape,
+    /// Times tiles can be split into finer ones, 0 for tiles of a single size.
+    pub subdivision_levels : usize,
+    /// Frequency of the noise deciding where tiles are split, per tile, when no density map does.
+    pub subdivision_scale : f32,
+}
+
 #[derive(Copy, Clone)]
 pub struct RandomWalkParameters {
-    pub smooth_number_of_points : usize,
-    pub smooth_sharpness : f32,
     pub slice_percentage : f32,
+    pub smoothing : SmoothingParameters,
+    /// Exponent applied to the tile density when picking the next tile, 0 for a uniform choice.
+    pub density_bias : f32,
+    /// Extra visits allowed on a fully dark tile.
+    pub density_revisits : usize,
+    /// How much dark walks move their smoothing sharpness toward 1.
+    pub density_sharpness : f32,
 }
 
 impl Grid {
@@ -41,17 +143,18 @@ impl Grid {
         Vec2::new(col as f32 * tile_scale * 2_f32 * std::f32::consts::FRAC_PI_6.cos(), row as f32 * tile_scale * 3_f32 * std::f32::consts::FRAC_PI_6.sin())   
     }
 
+    /// Pointy top hex grid of `col` by `row` tiles, odd rows shifted right by half a tile.
+    ///
+    /// Neighbors are listed in the order of `Hex::DIRECTIONS`.
     pub fn hex_grid(col : usize, row : usize, tile_scale : f32, base_position : Vec2) -> Grid {
         let mut grid = Grid {
             tiles: Vec::new(),
+            hex_tiles: HashMap::new(),
+            vertices: Vec::new(),
+            edges: Vec::new(),
             tile_scale,
         };
 
-        let max_x = col - 1;
-        let max_y = row - 1;
-
-        let o = 0_f32;
-        let i = 1_f32 * tile_scale;
         let si = std::f32::consts::FRAC_PI_6.sin() * tile_scale;
         let co = std::f32::consts::FRAC_PI_6.cos() * tile_scale;
 
@@ -62,79 +165,434 @@ impl Grid {
             for x in 0..col {
                 let mut position = base_position + Vec2::new(x as f32 * tile_width, y as f32 * tile_height);
                 if y % 2 == 1 {
-                    position.x = position.x + co;
+                    position.x += co;
                 }
 
+                let tile_index = grid.tiles.len();
+                let mut tile = TileInfo::new(tile_index, position);
+                tile.hex = Some(Hex::from_offset(x as i32, y as i32));
+                tile.vertices = Grid::hex_vertices(position, tile_scale);
+                grid.tiles.push(tile);
+            }
+        }
+
+        grid.index();
+        grid.link_hex_neighbors();
+        grid
+    }
+
+    fn hex_vertices(position : Vec2, tile_scale : f32) -> Vec<Vec2> {
+        let o = 0_f32;
+        let i = 1_f32 * tile_scale;
+        let si = std::f32::consts::FRAC_PI_6.sin() * tile_scale;
+        let co = std::f32::consts::FRAC_PI_6.cos() * tile_scale;
+
+        vec![
+            position + Vec2::new(o, i),
+            position + Vec2::new(-co, si),
+            position + Vec2::new(-co, -si),
+            position + Vec2::new(o, -i),
+            position + Vec2::new(co, -si),
+            position + Vec2::new(co, si),
+        ]
+    }
+
+    /// Pointy top hexagons at the given axial coordinates, `origin` being the center of the one at 0, 0.
+    ///
+    /// Neighbors are listed in the order of `Hex::DIRECTIONS`.
+    pub fn from_hexes(hexes : &[Hex], tile_scale : f32, origin : Vec2) -> Grid {
+        let mut grid = Grid {
+            tiles: Vec::new(),
+            hex_tiles: HashMap::new(),
+            vertices: Vec::new(),
+            edges: Vec::new(),
+            tile_scale,
+        };
+
+        for hex in hexes {
+            if grid.hex_tiles.contains_key(hex) {
+                continue;
+            }
+
+            let position = origin + hex.center(tile_scale);
+            let mut tile = TileInfo::new(grid.tiles.len(), position);
+            tile.hex = Some(*hex);
+            tile.vertices = Grid::hex_vertices(position, tile_scale);
+            grid.hex_tiles.insert(*hex, tile.index);
+            grid.tiles.push(tile);
+        }
+
+        grid.link_hex_neighbors();
+        grid.build_edges();
+        grid
+    }
+
+    /// Hexagon of tiles up to `radius` steps from the one centered on `center`.
+    pub fn hexagon_grid(radius : usize, tile_scale : f32, center : Vec2) -> Grid {
+        Grid::from_hexes(&Hex::default().spiral(radius as i32), tile_scale, center)
+    }
+
+    pub fn square_grid_size(col : usize, row : usize, tile_scale : f32) -> Vec2 {
+        Vec2::new(col as f32, row as f32) * tile_scale * 2_f32
+    }
+
+    /// Square tiles of `col` by `row`, `tile_scale` being half their side.
+    ///
+    /// Neighbors are the tiles on the left, right, above and below.
+    pub fn square_grid(col : usize, row : usize, tile_scale : f32, base_position : Vec2) -> Grid {
+        let mut grid = Grid {
+            tiles: Vec::new(),
+            hex_tiles: HashMap::new(),
+            vertices: Vec::new(),
+            edges: Vec::new(),
+            tile_scale,
+        };
+
+        for y in 0..row {
+            for x in 0..col {
+                let position = base_position + Vec2::new(x as f32 * 2_f32 + 1_f32, y as f32 * 2_f32 + 1_f32) * tile_scale;
                 let tile_index = grid.tiles.len();
                 let mut tile = TileInfo::new(tile_index, position);
 
                 if x > 0 {
                     tile.neighbors.push(tile_index - 1);
                 }
-                
-                if x < max_x {
+
+                if x + 1 < col {
                     tile.neighbors.push(tile_index + 1);
                 }
 
-                if y % 2 == 0 {
-                    if y > 0 {
-                        tile.neighbors.push(tile_index - col);
+                if y > 0 {
+                    tile.neighbors.push(tile_index - col);
+                }
 
-                        if x > 0 {
-                            tile.neighbors.push(tile_index - col - 1);
-                        }
-                    }
+                if y + 1 < row {
+                    tile.neighbors.push(tile_index + col);
+                }
 
-                    if y < max_y {
-                        tile.neighbors.push(tile_index + col);
+                tile.vertices = Grid::square_vertices(position, tile_scale);
+                grid.tiles.push(tile);
+            }
+        }
 
-                        if x > 0 {
-                            tile.neighbors.push(tile_index + col - 1);
-                        }
-                    }
+        grid.index();
+        grid
+    }
+
+    // Same winding as the hexagons, from the bottom left corner.
+    fn square_vertices(position : Vec2, half_side : f32) -> Vec<Vec2> {
+        vec![
+            position + Vec2::new(-half_side, half_side),
+            position + Vec2::new(-half_side, -half_side),
+            position + Vec2::new(half_side, -half_side),
+            position + Vec2::new(half_side, half_side),
+        ]
+    }
+
+    /// Grid of the given shape centered in an area of the given size.
+    pub fn centered_hex_grid(parameters: &GridParameters, area: Vec2) -> Grid {
+        match parameters.shape {
+            GridShape::Rectangle => {
+                let grid_size = Grid::hex_grid_size(parameters.col, parameters.row, parameters.tile_scale);
+                Grid::hex_grid(parameters.col, parameters.row, parameters.tile_scale, (area - grid_size) / 2_f32)
+            },
+            GridShape::Hexagon => {
+                let radius = (parameters.col.min(parameters.row).max(1) - 1) / 2;
+                Grid::hexagon_grid(radius, parameters.tile_scale, area / 2_f32)
+            },
+            GridShape::Square => {
+                let grid_size = Grid::square_grid_size(parameters.col, parameters.row, parameters.tile_scale);
+                Grid::square_grid(parameters.col, parameters.row, parameters.tile_scale, (area - grid_size) / 2_f32)
+            },
+        }
+    }
+
+    // Neighbors of the tiles placed on hexagons, in the order of `Hex::DIRECTIONS`.
+    fn link_hex_neighbors(&mut self) {
+        for tile in &mut self.tiles {
+            if let Some(hex) = tile.hex {
+                tile.neighbors = hex.neighbors().iter().filter_map(|neighbor| self.hex_tiles.get(neighbor).copied()).collect();
+            }
+        }
+    }
+
+    fn index(&mut self) {
+        self.hex_tiles = self.tiles.iter().filter_map(|tile| Some((tile.hex?, tile.index))).collect();
+        self.build_edges();
+    }
+
+    /// Merges the corners of the tiles into shared vertices and their sides into shared edges.
+    fn build_edges(&mut self) {
+        // Tiles compute their corners from their own center, so the same corner can differ by rounding.
+        let tolerance = self.tile_scale.abs().max(f32::MIN_POSITIVE) * 1e-3_f32;
+        let cell_of = |position: Vec2| ((position.x / tolerance).floor() as i64, (position.y / tolerance).floor() as i64);
+
+        let mut vertices : Vec<Vec2> = Vec::new();
+        let mut cells : HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        let mut edges : Vec<GridEdge> = Vec::new();
+        let mut edge_ids : HashMap<(usize, usize), usize> = HashMap::new();
+
+        for tile in &mut self.tiles {
+            tile.vertex_ids = tile.vertices.iter().map(|vertex| {
+                let (x, y) = cell_of(*vertex);
+                let existing = (x - 1..=x + 1)
+                    .flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y)))
+                    .filter_map(|cell| cells.get(&cell))
+                    .flatten()
+                    .find(|id| vertices[**id].distance(*vertex) <= tolerance)
+                    .copied();
+
+                existing.unwrap_or_else(|| {
+                    cells.entry((x, y)).or_default().push(vertices.len());
+                    vertices.push(*vertex);
+                    vertices.len() - 1
+                })
+            }).collect();
+
+            tile.edges.clear();
+            let count = tile.vertex_ids.len();
+            for corner in 0..count {
+                let (a, b) = (tile.vertex_ids[corner], tile.vertex_ids[(corner + 1) % count]);
+                if a == b {
+                    continue;
                 }
-                else {
-                    if y > 0 {
-                        tile.neighbors.push(tile_index - col);
 
-                        if x < max_x {
-                            tile.neighbors.push(tile_index - col + 1);
-                        }
+                let id = *edge_ids.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    edges.push(GridEdge { from: a.min(b), to: a.max(b), left: None, right: None });
+                    edges.len() - 1
+                });
+
+                // With y going down, a positive cross product puts the center on the right.
+                let edge = &mut edges[id];
+                match (vertices[edge.to] - vertices[edge.from]).perp_dot(tile.position - vertices[edge.from]) > 0_f32 {
+                    true => edge.right = Some(tile.index),
+                    false => edge.left = Some(tile.index),
+                }
+
+                tile.edges.push(id);
+            }
+        }
+
+        self.vertices = vertices;
+        self.edges = edges;
+    }
+
+    pub fn tile_at_hex(&self, hex: Hex) -> Option<usize> {
+        self.hex_tiles.get(&hex).copied()
+    }
+
+    /// Indexes of the tiles at the given coordinates, the ones outside of the grid skipped.
+    pub fn hex_tiles(&self, hexes: &[Hex]) -> Vec<usize> {
+        hexes.iter().filter_map(|hex| self.tile_at_hex(*hex)).collect()
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        let vertices : Vec<Vec2> = self.tiles.iter().flat_map(|tile| tile.vertices.iter().copied()).collect();
+        Bounds::from_points(&vertices)
+    }
+
+    /// Samples the density of every tile, dropping the ones lighter than the threshold.
+    pub fn apply_density<F>(&mut self, density: F, inclusion_threshold: f32) where F: Fn(Vec2) -> f32 {
+        for tile in &mut self.tiles {
+            tile.density = density(tile.position);
+        }
+
+        self.retain_tiles(|tile| tile.density >= inclusion_threshold);
+    }
+
+    /// Keeps the tiles matching the predicate, indexes and neighbors are remapped.
+    pub fn retain_tiles<F>(&mut self, predicate: F) where F: Fn(&TileInfo) -> bool {
+        let mut remap = vec![None; self.tiles.len()];
+        let mut tiles = Vec::new();
+        for tile in self.tiles.drain(..) {
+            if predicate(&tile) {
+                remap[tile.index] = Some(tiles.len());
+                tiles.push(tile);
+            }
+        }
+
+        for tile in &mut tiles {
+            tile.index = remap[tile.index].unwrap();
+            tile.neighbors = tile.neighbors.iter().filter_map(|neighbor| remap[*neighbor]).collect();
+        }
+
+        self.tiles = tiles;
+        self.index();
+    }
+
+    /// Splits the tiles matching the predicate, then the finer tiles matching it again, up to `levels` times.
+    ///
+    /// Quadrilaterals and triangles are split into four, other polygons into triangles around their center.
+    /// Corners of finer tiles along the sides of coarser ones become vertices of both, and neighbors are then
+    /// the tiles sharing an edge so walks cross from one size to the other.
+    pub fn subdivide<F>(&mut self, levels: usize, split: F) where F: Fn(&TileInfo) -> bool {
+        let mut changed = false;
+        for _ in 0..levels {
+            let mut tiles = Vec::new();
+            let mut split_any = false;
+            for tile in self.tiles.drain(..) {
+                if !split(&tile) {
+                    tiles.push(tile);
+                    continue;
+                }
+
+                split_any = true;
+                for corners in split_polygon(&polygon_corners(&tile.vertices)) {
+                    let mut child = TileInfo::new(tiles.len(), corners.iter().sum::<Vec2>() / corners.len() as f32);
+                    child.vertices = corners;
+                    child.density = tile.density;
+                    child.level = tile.level + 1;
+                    tiles.push(child);
+                }
+            }
+
+            for (index, tile) in tiles.iter_mut().enumerate() {
+                tile.index = index;
+            }
+
+            self.tiles = tiles;
+            changed |= split_any;
+            if !split_any {
+                break;
+            }
+        }
+
+        // Grids left whole keep their neighbors in the order walks were seeded with.
+        if changed {
+            self.insert_hanging_vertices();
+            self.index();
+            self.link_neighbors_by_edges();
+        }
+    }
+
+    // Corners lying on the side of another tile are added to its vertices so both tiles share the same edges.
+    fn insert_hanging_vertices(&mut self) {
+        let tolerance = self.tile_scale.abs().max(f32::MIN_POSITIVE) * 1e-3_f32;
+        let shortest_side = self.tiles.iter()
+            .flat_map(|tile| (0..tile.vertices.len()).map(|corner| tile.vertices[corner].distance(tile.vertices[(corner + 1) % tile.vertices.len()])))
+            .fold(f32::MAX, f32::min)
+            .max(tolerance);
+
+        let cell_of = |position: Vec2| ((position.x / shortest_side).floor() as i64, (position.y / shortest_side).floor() as i64);
+        let mut cells : HashMap<(i64, i64), Vec<Vec2>> = HashMap::new();
+        for vertex in self.tiles.iter().flat_map(|tile| tile.vertices.iter()) {
+            let cell = cells.entry(cell_of(*vertex)).or_default();
+            if !cell.iter().any(|other| other.distance(*vertex) <= tolerance) {
+                cell.push(*vertex);
+            }
+        }
+
+        for tile in &mut self.tiles {
+            let count = tile.vertices.len();
+            let mut vertices = Vec::with_capacity(count);
+            for corner in 0..count {
+                let (start, end) = (tile.vertices[corner], tile.vertices[(corner + 1) % count]);
+                vertices.push(start);
+
+                let side = end - start;
+                let length = side.length();
+                if length <= tolerance {
+                    continue;
+                }
+
+                let (min, max) = (cell_of(start.min(end)), cell_of(start.max(end)));
+                let mut along : Vec<(f32, Vec2)> = (min.0 - 1..=max.0 + 1)
+                    .flat_map(|x| (min.1 - 1..=max.1 + 1).map(move |y| (x, y)))
+                    .filter_map(|cell| cells.get(&cell))
+                    .flatten()
+                    .map(|vertex| ((*vertex - start).dot(side) / (length * length), *vertex))
+                    .filter(|(ratio, vertex)| {
+                        let distance_along = ratio * length;
+                        distance_along > tolerance && distance_along < length - tolerance && (*vertex - start).perp_dot(side).abs() / length <= tolerance
+                    })
+                    .collect();
+
+                along.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (_, vertex) in along {
+                    if vertices.last().map_or(true, |last: &Vec2| last.distance(vertex) > tolerance) {
+                        vertices.push(vertex);
                     }
+                }
+            }
 
-                    if y < max_y {
-                        tile.neighbors.push(tile_index + col);
+            tile.vertices = vertices;
+        }
+    }
 
-                        if x < max_x {
-                            tile.neighbors.push(tile_index + col + 1);
-                        }
+    // Tiles across each edge, in the order of the edges of the tile.
+    fn link_neighbors_by_edges(&mut self) {
+        for tile in &mut self.tiles {
+            tile.neighbors.clear();
+            for edge in &tile.edges {
+                if let Some(other) = self.edges[*edge].other_tile(tile.index) {
+                    if !tile.neighbors.contains(&other) {
+                        tile.neighbors.push(other);
                     }
                 }
-                
-                tile.vertices.clear();
-                tile.vertices.push(position + Vec2::new(o, i));
-                tile.vertices.push(position + Vec2::new(-co, si));
-                tile.vertices.push(position + Vec2::new(-co, -si));
-                tile.vertices.push(position + Vec2::new(o, -i));
-                tile.vertices.push(position + Vec2::new(co, -si));
-                tile.vertices.push(position + Vec2::new(co, si));
-                
-                grid.tiles.push(tile);
             }
         }
+    }
 
-        return grid;
+    /// Edge ids of each side of a tile, from one corner to the next in the order of its edges.
+    ///
+    /// A side running along finer tiles holds several edges, the vertices between them lying straight between the corners.
+    pub fn tile_sides(&self, tile: usize) -> Vec<Vec<usize>> {
+        let tile = &self.tiles[tile];
+        let corners = polygon_corners(&tile.vertices);
+        // Vertex each edge starts from, sides between merged corners having no edge.
+        let count = tile.vertex_ids.len();
+        let starts : Vec<usize> = (0..count).filter(|corner| tile.vertex_ids[*corner] != tile.vertex_ids[(corner + 1) % count]).collect();
+        let is_corner = |edge: usize| corners.contains(&tile.vertices[starts[edge]]);
+
+        let first = (0..tile.edges.len()).find(|edge| is_corner(*edge)).unwrap_or(0);
+        let mut sides : Vec<Vec<usize>> = Vec::new();
+        for offset in 0..tile.edges.len() {
+            let edge = (first + offset) % tile.edges.len();
+            match sides.last_mut() {
+                Some(side) if !is_corner(edge) => side.push(tile.edges[edge]),
+                _ => sides.push(vec![tile.edges[edge]]),
+            }
+        }
+
+        sides
+    }
+
+    pub fn tile_at(&self, position: Vec2) -> Option<usize> {
+        self.tiles.iter().find(|tile| polygon_contains(&tile.vertices, position)).map(|tile| tile.index)
     }
 
-    pub fn random_walk(&self, parameters: RandomWalkParameters,instructions: &mut Vec<Instruction>) {
-        let mut random = rand::thread_rng();
-        let mut used_indexes = HashSet::new();
-        let mut unused_indexes: Vec<usize> = (0..self.tiles.len()).collect();
-        let mut tile_remaining = self.tiles.len();
+    /// The walks as lists of tile indexes, longest first, without the shortest ones cut by the slice percentage.
+    pub fn random_walk<R: Rng>(&self, random: &mut R, parameters: RandomWalkParameters, constraints: &TileConstraints) -> Vec<Vec<usize>> {
+        let mut remaining_visits : Vec<usize> = self.tiles.iter().map(|tile| match constraints.excluded.contains(&tile.index) {
+            true => 0,
+            false => 1 + (tile.density * parameters.density_revisits as f32).round() as usize,
+        }).collect();
+
+        let mut unused_indexes: Vec<usize> = (0..self.tiles.len()).filter(|index| remaining_visits[*index] > 0).collect();
+        let total_visits : usize = remaining_visits.iter().sum();
+        let mut tile_remaining = total_visits;
+        if tile_remaining == 0 {
+            return Vec::new();
+        }
+
+        let mut starts : VecDeque<usize> = constraints.starts.iter().copied().filter(|start| *start < self.tiles.len()).collect();
+        let mut next_start = |remaining_visits: &[usize]| {
+            while let Some(start) = starts.pop_front() {
+                if remaining_visits[start] > 0 {
+                    return Some(start);
+                }
+            }
+
+            None
+        };
 
-        let mut current_index = random.gen_range(0..self.tiles.len());
+        let weight = |index: &usize| match parameters.density_bias {
+            bias if bias > 0_f32 => self.tiles[*index].density.max(0.01_f32).powf(bias).max(f32::MIN_POSITIVE),
+            _ => 1_f32,
+        };
 
-        instructions.push(Instruction::MoveTo(self.tiles[current_index].position));
+        let mut current_index = next_start(&remaining_visits).unwrap_or_else(|| unused_indexes[WeightedIndex::new(unused_indexes.iter().map(weight)).unwrap().sample(random)]);
+        let mut previous_index = None;
 
         let frame = 100;
         let mut counter = frame;
@@ -142,32 +600,36 @@ impl Grid {
         let mut walks = Vec::new();
         let mut current_walk = Vec::new();
 
-        current_walk.push(self.tiles[current_index].position);
+        current_walk.push(current_index);
 
         while tile_remaining > 0 {
-            used_indexes.insert(current_index);
-            unused_indexes.swap_remove(unused_indexes.iter().position(|&i| i == current_index).unwrap());
-            tile_remaining = tile_remaining - 1;
+            remaining_visits[current_index] -= 1;
+            if remaining_visits[current_index] == 0 {
+                unused_indexes.swap_remove(unused_indexes.iter().position(|&i| i == current_index).unwrap());
+            }
 
-            let valid_neighbors : Vec<&usize> = self.tiles[current_index].neighbors.iter().filter(|&it| !used_indexes.contains(it)).collect();
-            if valid_neighbors.len() > 0 {
-                let neighbor_index = *valid_neighbors[random.gen_range(0..valid_neighbors.len())];
+            tile_remaining -= 1;
+
+            let valid_neighbors : Vec<usize> = self.tiles[current_index].neighbors.iter().copied().filter(|it| remaining_visits[*it] > 0 && Some(*it) != previous_index).collect();
+            if !valid_neighbors.is_empty() {
+                let neighbor_index = valid_neighbors[WeightedIndex::new(valid_neighbors.iter().map(weight)).unwrap().sample(random)];
+                previous_index = Some(current_index);
                 current_index = self.tiles[neighbor_index].index;
                 
-                current_walk.push(self.tiles[current_index].position);
+                current_walk.push(current_index);
             }
             else if tile_remaining > 0 {
-                current_index = unused_indexes[random.gen_range(0..unused_indexes.len())];
+                current_index = next_start(&remaining_visits).unwrap_or_else(|| unused_indexes[WeightedIndex::new(unused_indexes.iter().map(weight)).unwrap().sample(random)]);
+                previous_index = None;
                 
                 walks.push(current_walk);
-                current_walk = Vec::new();
-                current_walk.push(self.tiles[current_index].position);
+                current_walk = vec![current_index];
             }
 
-            counter = counter - 1;
+            counter -= 1;
             if counter < 1 {
                 counter = frame;
-                println!("{:.3} : {} / {}", tile_remaining as f32 / self.tiles.len() as f32, tile_remaining, self.tiles.len());
+                println!("{:.3} : {} / {}", tile_remaining as f32 / total_visits as f32, tile_remaining, total_visits);
             }
         }
 
@@ -176,20 +638,22 @@ impl Grid {
             let cmp = a.len().cmp(&b.len());
             match cmp {
                 Ordering::Equal => {
-                    let delta = b[0].x - a[0].x;
-                    if delta < -std::f32::MIN_POSITIVE {
+                    let a = self.tiles[a[0]].position;
+                    let b = self.tiles[b[0]].position;
+                    let delta = b.x - a.x;
+                    if delta < -f32::MIN_POSITIVE {
                         Ordering::Greater
                     }
-                    else if delta > std::f32::MIN_POSITIVE {
+                    else if delta > f32::MIN_POSITIVE {
                         Ordering::Less
                     }
                     else
                     {
-                        let delta = b[0].y - a[0].y;
-                        if delta < -std::f32::MIN_POSITIVE {
+                        let delta = b.y - a.y;
+                        if delta < -f32::MIN_POSITIVE {
                             Ordering::Greater
                         }
-                        else if delta > std::f32::MIN_POSITIVE {
+                        else if delta > f32::MIN_POSITIVE {
                             Ordering::Less
                         }
                         else {
@@ -204,24 +668,316 @@ impl Grid {
             }
         });
 
-        walks = walks.into_iter().map(|w| smooth(w, parameters.smooth_number_of_points, parameters.smooth_sharpness)).collect();
-        let (_, half) = walks.split_at((walks.len() as f32 * parameters.slice_percentage).round() as usize);
-        let walks = half;
+        let slice_index = (walks.len() as f32 * parameters.slice_percentage).round() as usize;
+        walks.into_iter().skip(slice_index).collect()
+    }
 
+    /// Returns the indexes of the tiles crossed by the walks that made it into the drawing.
+    pub fn draw_walks(&self, walks: &[Vec<usize>], parameters: RandomWalkParameters, attributes: &StrokeAttributes, drawing: &mut Drawing) -> Vec<usize> {
+        let mut visited = Vec::new();
         for walk in walks {
-            if walk.len() < 1 {
+            if walk.len() < 2 {
+                // print_circle_to_drawing(self.tiles[walk[0]].position, self.tile_scale / 2_f32, 8, attributes, drawing);
+
                 continue;
             }
 
-            if walk.len() < 2 {
-                // print_circle_to_instructions(walk[0], self.tile_scale / 2_f32, 8, instructions);
+            visited.extend(walk.iter());
+            let darkness = walk.iter().map(|index| self.tiles[*index].density).sum::<f32>() / walk.len() as f32;
+            let smoothing = parameters.smoothing;
+            let sharpness = smoothing.smooth_sharpness + (1_f32 - smoothing.smooth_sharpness) * (darkness * parameters.density_sharpness).clamp(0_f32, 1_f32);
+            let walk = walk.iter().map(|index| self.tiles[*index].position).collect();
+            let mut instructions = Vec::new();
+            smooth_into_instructions(walk, smoothing.smoothing_mode, smoothing.smooth_number_of_points, sharpness, &mut instructions);
+            drawing.add_instructions(&instructions, attributes);
+        }
+
+        visited
+    }
+
+    /// Outlines of every tile, each side drawn once.
+    pub fn print_to_drawing(&self, attributes: &StrokeAttributes, drawing: &mut Drawing) {
+        let mut instructions = Vec::new();
+        for stroke in self.edge_strokes() {
+            instructions.push(Instruction::MoveTo(self.vertices[stroke[0]]));
+            instructions.extend(stroke[1..].iter().map(|id| Instruction::LineTo(self.vertices[*id])));
+        }
+
+        drawing.add_instructions(&instructions, attributes);
+    }
+
+    /// Covers every edge exactly once with continuous strokes of vertex ids, as few as the graph allows.
+    ///
+    /// Odd vertices of each connected part are paired, nearest first, with pen up moves making the part Eulerian.
+    /// An Eulerian circuit is then walked with Hierholzer's algorithm and cut at the pen up moves.
+    pub fn edge_strokes(&self) -> Vec<Vec<usize>> {
+        // Edges as (edge, other end) around each vertex, pen up moves numbered after the grid edges.
+        let mut adjacency : Vec<Vec<(usize, usize)>> = vec![Vec::new(); self.vertices.len()];
+        for (id, edge) in self.edges.iter().enumerate() {
+            adjacency[edge.from].push((id, edge.to));
+            adjacency[edge.to].push((id, edge.from));
+        }
 
+        let mut component = vec![usize::MAX; self.vertices.len()];
+        let mut components : Vec<Vec<usize>> = Vec::new();
+        for start in 0..self.vertices.len() {
+            if component[start] != usize::MAX || adjacency[start].is_empty() {
                 continue;
             }
 
-            instructions.push(Instruction::MoveTo(walk[0]));
-            for index in 1..walk.len() {
-                instructions.push(Instruction::LineTo(walk[index]));
+            let mut members = vec![start];
+            component[start] = components.len();
+            let mut next = 0;
+            while next < members.len() {
+                for (_, other) in &adjacency[members[next]] {
+                    if component[*other] == usize::MAX {
+                        component[*other] = components.len();
+                        members.push(*other);
+                    }
+                }
+
+                next += 1;
+            }
+
+            components.push(members);
+        }
+
+        let mut edge_count = self.edges.len();
+        let mut starts = Vec::new();
+        for members in &components {
+            let mut odd : Vec<usize> = members.iter().copied().filter(|vertex| adjacency[*vertex].len() % 2 == 1).collect();
+            odd.sort_unstable();
+            starts.push(odd.first().copied().unwrap_or(members[0]));
+            while let Some(vertex) = odd.pop() {
+                let nearest = odd.iter().enumerate()
+                    .min_by(|(_, a), (_, b)| self.vertices[**a].distance_squared(self.vertices[vertex]).total_cmp(&self.vertices[**b].distance_squared(self.vertices[vertex])))
+                    .map(|(position, _)| position);
+                if let Some(position) = nearest {
+                    let other = odd.swap_remove(position);
+                    adjacency[vertex].push((edge_count, other));
+                    adjacency[other].push((edge_count, vertex));
+                    edge_count += 1;
+                }
+            }
+        }
+
+        let is_pen_up = |edge: usize| edge >= self.edges.len();
+        let mut used = vec![false; edge_count];
+        let mut next_edge = vec![0_usize; self.vertices.len()];
+        let mut strokes = Vec::new();
+        for start in starts {
+            // Vertices of the circuit, each with the edge it was reached through.
+            let mut circuit : Vec<(usize, Option<usize>)> = Vec::new();
+            let mut stack = vec![(start, None)];
+            while let Some(&(vertex, _)) = stack.last() {
+                while next_edge[vertex] < adjacency[vertex].len() && used[adjacency[vertex][next_edge[vertex]].0] {
+                    next_edge[vertex] += 1;
+                }
+
+                match adjacency[vertex].get(next_edge[vertex]) {
+                    Some(&(edge, other)) => {
+                        used[edge] = true;
+                        stack.push((other, Some(edge)));
+                    },
+                    None => circuit.push(stack.pop().unwrap()),
+                }
+            }
+
+            circuit.reverse();
+
+            // Starting right after a pen up move, so a stroke never wraps around the end of the circuit.
+            let steps = &circuit[1..];
+            let first = steps.iter().position(|(_, edge)| edge.is_some_and(is_pen_up)).map_or(0, |position| position + 1);
+            let mut stroke = vec![match first { 0 => circuit[0].0, _ => steps[first - 1].0 }];
+            for (vertex, edge) in steps[first..].iter().chain(steps[..first].iter()) {
+                if edge.is_some_and(is_pen_up) {
+                    strokes.push(std::mem::replace(&mut stroke, vec![*vertex]));
+                }
+                else {
+                    stroke.push(*vertex);
+                }
+            }
+
+            strokes.push(stroke);
+        }
+
+        strokes.retain(|stroke| stroke.len() > 1);
+        strokes
+    }
+}
+
+// Vertices of a polygon without the ones lying straight between their neighbors.
+fn polygon_corners(vertices: &[Vec2]) -> Vec<Vec2> {
+    let count = vertices.len();
+    let corners : Vec<Vec2> = (0..count).filter(|index| {
+        let (previous, vertex, next) = (vertices[(index + count - 1) % count], vertices[*index], vertices[(index + 1) % count]);
+        let (incoming, outgoing) = (vertex - previous, next - vertex);
+        incoming.perp_dot(outgoing).abs() > 1e-4_f32 * incoming.length() * outgoing.length() || incoming.dot(outgoing) < 0_f32
+    }).map(|index| vertices[index]).collect();
+
+    match corners.len() < 3 {
+        true => vertices.to_vec(),
+        false => corners,
+    }
+}
+
+// Finer polygons covering a polygon, wound the same way.
+fn split_polygon(corners: &[Vec2]) -> Vec<Vec<Vec2>> {
+    let count = corners.len();
+    let middle = |index: usize| (corners[index % count] + corners[(index + 1) % count]) / 2_f32;
+    let center = corners.iter().sum::<Vec2>() / count as f32;
+    match count {
+        3 => vec![
+            vec![corners[0], middle(0), middle(2)],
+            vec![middle(0), corners[1], middle(1)],
+            vec![middle(2), middle(1), corners[2]],
+            vec![middle(0), middle(1), middle(2)],
+        ],
+        4 => (0..4).map(|index| vec![corners[index], middle(index), center, middle(index + 3)]).collect(),
+        _ => (0..count).map(|index| vec![center, corners[index], corners[(index + 1) % count]]).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grids() -> Vec<Grid> {
+        let mut subdivided = Grid::hex_grid(4, 3, 10_f32, Vec2::ZERO);
+        subdivided.subdivide(2, |tile| tile.position.x < 30_f32);
+        assert!(subdivided.tiles.iter().any(|tile| tile.level == 2));
+
+        // Two groups of hexagons not touching each other.
+        let mut hexes = Hex::default().spiral(1);
+        hexes.extend(Hex::new(6, 0).spiral(1));
+
+        vec![
+            Grid::hex_grid(5, 4, 10_f32, Vec2::ZERO),
+            Grid::square_grid(4, 3, 10_f32, Vec2::ZERO),
+            Grid::hexagon_grid(2, 10_f32, Vec2::ZERO),
+            Grid::from_hexes(&hexes, 10_f32, Vec2::ZERO),
+            subdivided,
+        ]
+    }
+
+    fn edge_id(grid: &Grid, a: usize, b: usize) -> Option<usize> {
+        grid.edges.iter().position(|edge| (edge.from, edge.to) == (a.min(b), a.max(b)))
+    }
+
+    #[test]
+    fn edge_strokes_cover_every_edge_once() {
+        for grid in grids() {
+            let mut covered = vec![0; grid.edges.len()];
+            for stroke in grid.edge_strokes() {
+                for pair in stroke.windows(2) {
+                    let id = edge_id(&grid, pair[0], pair[1]).expect("strokes only follow edges of the grid");
+                    covered[id] += 1;
+                }
+            }
+
+            assert!(covered.iter().all(|count| *count == 1), "{:?}", covered);
+        }
+    }
+
+    // Representative of the connected part of a vertex.
+    fn root(part: &mut [usize], vertex: usize) -> usize {
+        match part[vertex] == vertex {
+            true => vertex,
+            false => {
+                let root = root(part, part[vertex]);
+                part[vertex] = root;
+                root
+            },
+        }
+    }
+
+    #[test]
+    fn edge_strokes_pair_odd_vertices() {
+        for grid in grids() {
+            // Connected parts of the vertices, merged along the edges.
+            let mut part : Vec<usize> = (0..grid.vertices.len()).collect();
+            let mut degrees = vec![0; grid.vertices.len()];
+            for edge in &grid.edges {
+                degrees[edge.from] += 1;
+                degrees[edge.to] += 1;
+                let (a, b) = (root(&mut part, edge.from), root(&mut part, edge.to));
+                part[a] = b;
+            }
+
+            let mut odd_counts : HashMap<usize, usize> = HashMap::new();
+            for vertex in (0..grid.vertices.len()).filter(|vertex| degrees[*vertex] > 0) {
+                let root = root(&mut part, vertex);
+                *odd_counts.entry(root).or_default() += degrees[vertex] % 2;
+            }
+
+            let expected : usize = odd_counts.values().map(|odd| (odd / 2).max(1)).sum();
+            assert_eq!(grid.edge_strokes().len(), expected);
+        }
+    }
+
+    fn subdivided_grid() -> Grid {
+        let mut grid = Grid::square_grid(4, 3, 10_f32, Vec2::ZERO);
+        grid.subdivide(2, |tile| tile.position.x < 40_f32 && tile.position.y < 40_f32);
+        grid
+    }
+
+    #[test]
+    fn subdivided_neighbors_are_symmetric() {
+        let grid = subdivided_grid();
+        for tile in &grid.tiles {
+            for neighbor in &tile.neighbors {
+                assert!(grid.tiles[*neighbor].neighbors.contains(&tile.index), "{} lists {} but not the other way", tile.index, neighbor);
+            }
+        }
+    }
+
+    #[test]
+    fn fine_and_coarse_tiles_share_edges() {
+        let grid = subdivided_grid();
+        let mut pairs = 0;
+        for tile in &grid.tiles {
+            for neighbor in tile.neighbors.iter().map(|index| &grid.tiles[*index]).filter(|neighbor| neighbor.level > tile.level) {
+                pairs += 1;
+                let shared : Vec<&usize> = neighbor.edges.iter().filter(|edge| tile.edges.contains(edge)).collect();
+                assert!(!shared.is_empty(), "tiles {} and {} share no edge id", tile.index, neighbor.index);
+
+                // The coarse side is cut where the finer tiles meet, so its edges are as long as theirs.
+                for edge in shared {
+                    let edge = grid.edges[*edge];
+                    let length = grid.vertices[edge.from].distance(grid.vertices[edge.to]);
+                    assert!((length - 20_f32 / 2_f32.powi(neighbor.level as i32)).abs() < 1e-3_f32);
+                }
+            }
+        }
+
+        assert!(pairs > 0);
+    }
+
+    #[test]
+    fn tile_sides_go_from_corner_to_corner() {
+        let grid = subdivided_grid();
+        for tile in &grid.tiles {
+            let sides = grid.tile_sides(tile.index);
+            assert_eq!(sides.len(), 4);
+            assert_eq!(sides.iter().map(|side| side.len()).sum::<usize>(), tile.edges.len());
+        }
+    }
+
+    #[test]
+    fn shared_sides_have_a_tile_on_each_side() {
+        for grid in grids() {
+            for tile in &grid.tiles {
+                for neighbor in &tile.neighbors {
+                    let shared : Vec<&GridEdge> = tile.edges.iter().map(|id| &grid.edges[*id]).filter(|edge| edge.other_tile(tile.index) == Some(*neighbor)).collect();
+                    assert!(!shared.is_empty(), "tiles {} and {} share no edge", tile.index, neighbor);
+                    for edge in shared {
+                        assert!(edge.left.is_some() && edge.right.is_some() && edge.left != edge.right);
+                    }
+                }
+            }
+
+            for edge in &grid.edges {
+                assert!(edge.left.is_some() || edge.right.is_some());
             }
         }
     }
@@ -0,0 +1,258 @@
+use glam::*;
+
+use crate::drawing::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HatchPattern {
+    Parallel,
+    CrossHatch,
+    Concentric,
+    Spiral,
+}
+
+impl HatchPattern {
+    pub fn next(self) -> HatchPattern {
+        match self {
+            HatchPattern::Parallel => HatchPattern::CrossHatch,
+            HatchPattern::CrossHatch => HatchPattern::Concentric,
+            HatchPattern::Concentric => HatchPattern::Spiral,
+            HatchPattern::Spiral => HatchPattern::Parallel,
+        }
+    }
+
+    pub fn from_index(index: usize) -> HatchPattern {
+        match index {
+            1 => HatchPattern::CrossHatch,
+            2 => HatchPattern::Concentric,
+            3 => HatchPattern::Spiral,
+            _ => HatchPattern::Parallel,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            HatchPattern::Parallel => 0,
+            HatchPattern::CrossHatch => 1,
+            HatchPattern::Concentric => 2,
+            HatchPattern::Spiral => 3,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HatchTarget {
+    VisitedTiles,
+    /// Each tile shaded with the probability of the parameters.
+    RandomTiles,
+    ImportedShapes,
+}
+
+impl HatchTarget {
+    pub fn parse(name: &str) -> Option<HatchTarget> {
+        match name {
+            "visited" => Some(HatchTarget::VisitedTiles),
+            "random" => Some(HatchTarget::RandomTiles),
+            "imported" => Some(HatchTarget::ImportedShapes),
+            _ => None,
+        }
+    }
+
+    pub fn from_index(index: usize) -> HatchTarget {
+        match index {
+            1 => HatchTarget::RandomTiles,
+            2 => HatchTarget::ImportedShapes,
+            _ => HatchTarget::VisitedTiles,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            HatchTarget::VisitedTiles => 0,
+            HatchTarget::RandomTiles => 1,
+            HatchTarget::ImportedShapes => 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct HatchParameters {
+    pub pattern : HatchPattern,
+    /// Nothing is hatched without a target.
+    pub target : Option<HatchTarget>,
+    /// Distance between two lines in millimetres.
+    pub spacing : f32,
+    /// Angle of the lines in degrees, the second pass of a cross-hatch being perpendicular.
+    pub angle : f32,
+    /// Probability for each tile to be shaded when hatching random tiles.
+    pub probability : f32,
+}
+
+impl Default for HatchParameters {
+    fn default() -> HatchParameters {
+        HatchParameters {
+            pattern: HatchPattern::Parallel,
+            target: None,
+            spacing: 0.8_f32,
+            angle: 45_f32,
+            probability: 0.3_f32,
+        }
+    }
+}
+
+/// Fills the polygon with hatch lines `spacing` apart, `angle` in degrees.
+pub fn hatch_polygon(polygon : &[Vec2], pattern : HatchPattern, spacing : f32, angle : f32, attributes : &StrokeAttributes, drawing : &mut Drawing) {
+    if polygon.len() < 3 || spacing <= 0_f32 {
+        return;
+    }
+
+    match pattern {
+        HatchPattern::Parallel => parallel_hatch(polygon, spacing, angle, attributes, drawing),
+        HatchPattern::CrossHatch => {
+            parallel_hatch(polygon, spacing, angle, attributes, drawing);
+            parallel_hatch(polygon, spacing, angle + 90_f32, attributes, drawing);
+        },
+        HatchPattern::Concentric => concentric_hatch(polygon, spacing, attributes, drawing),
+        HatchPattern::Spiral => spiral_hatch(polygon, spacing, attributes, drawing),
+    }
+}
+
+// Scanlines in the rotated frame of the polygon, alternating direction so the pen travels less between lines.
+fn parallel_hatch(polygon : &[Vec2], spacing : f32, angle : f32, attributes : &StrokeAttributes, drawing : &mut Drawing) {
+    let rotation = Mat2::from_angle(angle.to_radians());
+    let inverse = rotation.transpose();
+    let rotated : Vec<Vec2> = polygon.iter().map(|point| inverse * *point).collect();
+    let bounds = Bounds::from_points(&rotated);
+
+    let mut y = bounds.min.y + spacing / 2_f32;
+    let mut reversed = false;
+    while y < bounds.max.y {
+        let mut crossings = Vec::new();
+        for index in 0..rotated.len() {
+            let a = rotated[index];
+            let b = rotated[(index + 1) % rotated.len()];
+            if (a.y > y) != (b.y > y) {
+                crossings.push(a.x + (y - a.y) / (b.y - a.y) * (b.x - a.x));
+            }
+        }
+
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        let mut segments : Vec<(f32, f32)> = crossings.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect();
+        if reversed {
+            segments = segments.into_iter().rev().map(|(start, end)| (end, start)).collect();
+        }
+
+        for (start, end) in segments {
+            let start = rotation * Vec2::new(start, y);
+            let end = rotation * Vec2::new(end, y);
+            drawing.extend(Stroke::from_polyline(&[start, end], attributes.clone()));
+        }
+
+        reversed = !reversed;
+        y += spacing;
+    }
+}
+
+fn signed_area(polygon : &[Vec2]) -> f32 {
+    let mut area = 0_f32;
+    for index in 0..polygon.len() {
+        area += polygon[index].perp_dot(polygon[(index + 1) % polygon.len()]);
+    }
+
+    area / 2_f32
+}
+
+/// Moves every edge inward by `distance`, vertices following the miter of their two edges.
+/// Returns None once the polygon has collapsed or turned itself inside out.
+pub fn inset_polygon(polygon : &[Vec2], distance : f32) -> Option<Vec<Vec2>> {
+    let area = signed_area(polygon);
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+
+    // Inward normals point left of the edges for a counter clockwise polygon.
+    let orientation = area.signum();
+    let count = polygon.len();
+    let mut inset = Vec::with_capacity(count);
+    for index in 0..count {
+        let previous = polygon[(index + count - 1) % count];
+        let current = polygon[index];
+        let next = polygon[(index + 1) % count];
+
+        let normal1 = (current - previous).normalize_or_zero().perp() * orientation;
+        let normal2 = (next - current).normalize_or_zero().perp() * orientation;
+        let bisector = (normal1 + normal2).normalize_or_zero();
+        let cos = bisector.dot(normal1).max(0.2_f32);
+        inset.push(current + bisector * distance / cos);
+    }
+
+    for index in 0..count {
+        let edge = polygon[(index + 1) % count] - polygon[index];
+        let inset_edge = inset[(index + 1) % count] - inset[index];
+        if edge.dot(inset_edge) <= 0_f32 {
+            return None;
+        }
+    }
+
+    let inset_area = signed_area(&inset);
+    match inset_area.signum() == area.signum() && inset_area.abs() < area.abs() {
+        true => Some(inset),
+        false => None,
+    }
+}
+
+fn concentric_hatch(polygon : &[Vec2], spacing : f32, attributes : &StrokeAttributes, drawing : &mut Drawing) {
+    let closed = StrokeAttributes { closed: true, ..attributes.clone() };
+    let mut current = inset_polygon(polygon, spacing / 2_f32);
+    while let Some(ring) = current {
+        drawing.extend(Stroke::from_polyline(&ring, closed.clone()));
+        current = inset_polygon(&ring, spacing);
+    }
+}
+
+// Walks the outline while shrinking it toward its centroid, one turn per `spacing`.
+// Only looks right on shapes that are star-shaped around their centroid, such as tiles.
+fn spiral_hatch(polygon : &[Vec2], spacing : f32, attributes : &StrokeAttributes, drawing : &mut Drawing) {
+    let centroid = polygon.iter().sum::<Vec2>() / polygon.len() as f32;
+    let mut inner_radius = f32::MAX;
+    let mut perimeter = 0_f32;
+    for index in 0..polygon.len() {
+        let a = polygon[index];
+        let b = polygon[(index + 1) % polygon.len()];
+        let edge = b - a;
+        let t = ((centroid - a).dot(edge) / edge.length_squared().max(f32::EPSILON)).clamp(0_f32, 1_f32);
+        inner_radius = inner_radius.min(centroid.distance(a + edge * t));
+        perimeter += edge.length();
+    }
+
+    if inner_radius < spacing {
+        return;
+    }
+
+    let turns = inner_radius / spacing;
+    let samples_per_edge = 4;
+    let mut points = Vec::new();
+    let mut travelled = 0_f32;
+    'spiral: loop {
+        for index in 0..polygon.len() {
+            let a = polygon[index];
+            let b = polygon[(index + 1) % polygon.len()];
+            let edge_length = a.distance(b);
+            for sample in 0..samples_per_edge {
+                let s = sample as f32 / samples_per_edge as f32;
+                // Starts half a turn inside so the spiral does not retrace the outline.
+                let shrink = (0.5_f32 + (travelled + edge_length * s) / perimeter) / turns;
+                if shrink >= 1_f32 {
+                    break 'spiral;
+                }
+
+                points.push(centroid + (a.lerp(b, s) - centroid) * (1_f32 - shrink));
+            }
+
+            travelled += edge_length;
+        }
+    }
+
+    if points.len() > 1 {
+        drawing.extend(Stroke::from_polyline(&points, attributes.clone()));
+    }
+}
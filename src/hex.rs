@@ -0,0 +1,254 @@
+use glam::*;
+
+/// Axial coordinates of a pointy top hexagon, `q` growing to the right and `r` downward.
+///
+/// The third cube coordinate `s` is implied by `q + r + s = 0`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Hex {
+    pub q : i32,
+    pub r : i32,
+}
+
+impl Hex {
+    /// Neighbor offsets, counterclockwise on screen starting from the right.
+    pub const DIRECTIONS : [Hex; 6] = [
+        Hex { q: 1, r: 0 },
+        Hex { q: 1, r: -1 },
+        Hex { q: 0, r: -1 },
+        Hex { q: -1, r: 0 },
+        Hex { q: -1, r: 1 },
+        Hex { q: 0, r: 1 },
+    ];
+
+    pub const fn new(q: i32, r: i32) -> Hex {
+        Hex { q, r }
+    }
+
+    pub fn s(self) -> i32 {
+        -self.q - self.r
+    }
+
+    /// From the column and row of a grid whose odd rows are shifted right by half a tile.
+    pub fn from_offset(col: i32, row: i32) -> Hex {
+        Hex::new(col - (row - (row & 1)) / 2, row)
+    }
+
+    /// Column and row in a grid whose odd rows are shifted right by half a tile.
+    pub fn offset(self) -> (i32, i32) {
+        (self.q + (self.r - (self.r & 1)) / 2, self.r)
+    }
+
+    /// Center relative to the hexagon at the origin, `size` being the distance from the center to a corner.
+    pub fn center(self, size: f32) -> Vec2 {
+        let sqrt_3 = 3_f32.sqrt();
+        Vec2::new(sqrt_3 * self.q as f32 + sqrt_3 / 2_f32 * self.r as f32, 1.5_f32 * self.r as f32) * size
+    }
+
+    /// Hexagon containing a position relative to the center of the hexagon at the origin.
+    pub fn at(position: Vec2, size: f32) -> Hex {
+        let q = (3_f32.sqrt() / 3_f32 * position.x - position.y / 3_f32) / size;
+        let r = 2_f32 / 3_f32 * position.y / size;
+        Hex::round(q, r)
+    }
+
+    /// Nearest hexagon to fractional axial coordinates.
+    pub fn round(q: f32, r: f32) -> Hex {
+        let s = -q - r;
+        let (mut rounded_q, mut rounded_r, rounded_s) = (q.round(), r.round(), s.round());
+        let (q_difference, r_difference, s_difference) = ((rounded_q - q).abs(), (rounded_r - r).abs(), (rounded_s - s).abs());
+        if q_difference > r_difference && q_difference > s_difference {
+            rounded_q = -rounded_r - rounded_s;
+        }
+        else if r_difference > s_difference {
+            rounded_r = -rounded_q - rounded_s;
+        }
+
+        Hex::new(rounded_q as i32, rounded_r as i32)
+    }
+
+    pub fn neighbor(self, direction: usize) -> Hex {
+        self + Hex::DIRECTIONS[direction % 6]
+    }
+
+    pub fn neighbors(self) -> [Hex; 6] {
+        Hex::DIRECTIONS.map(|direction| self + direction)
+    }
+
+    /// Number of steps between two hexagons.
+    pub fn distance(self, other: Hex) -> i32 {
+        let difference = self - other;
+        (difference.q.abs() + difference.r.abs() + difference.s().abs()) / 2
+    }
+
+    /// Hexagons at exactly `radius` steps, counterclockwise on screen, the center alone for a radius of 0.
+    pub fn ring(self, radius: i32) -> Vec<Hex> {
+        if radius <= 0 {
+            return vec![self];
+        }
+
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        let mut hex = self + Hex::DIRECTIONS[4] * radius;
+        for direction in 0..6 {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex.neighbor(direction);
+            }
+        }
+
+        ring
+    }
+
+    /// Hexagons up to `radius` steps, ring after ring from the center.
+    pub fn spiral(self, radius: i32) -> Vec<Hex> {
+        (0..=radius.max(0)).flat_map(|ring| self.ring(ring)).collect()
+    }
+
+    /// Hexagons crossed by the straight line between two centers, both ends included.
+    pub fn line(self, other: Hex) -> Vec<Hex> {
+        let steps = self.distance(other);
+        // Nudged so lines along edges fall on the same side every time.
+        let (q, r) = (self.q as f32 + 1e-6_f32, self.r as f32 + 2e-6_f32);
+        let (other_q, other_r) = (other.q as f32 + 1e-6_f32, other.r as f32 + 2e-6_f32);
+        (0..=steps).map(|step| {
+            let ratio = match steps { 0 => 0_f32, _ => step as f32 / steps as f32 };
+            Hex::round(q + (other_q - q) * ratio, r + (other_r - r) * ratio)
+        }).collect()
+    }
+
+    /// Rotated by `steps` sixths of a turn around `center`, counterclockwise on screen for positive steps.
+    pub fn rotate(self, center: Hex, steps: i32) -> Hex {
+        let mut relative = self - center;
+        for _ in 0..steps.rem_euclid(6) {
+            relative = Hex::new(-relative.s(), -relative.q);
+        }
+
+        center + relative
+    }
+
+    /// Mirrored around `center` along the axis keeping `q` constant.
+    pub fn reflect_q(self, center: Hex) -> Hex {
+        let relative = self - center;
+        center + Hex::new(relative.q, relative.s())
+    }
+}
+
+impl std::ops::Add for Hex {
+    type Output = Hex;
+
+    fn add(self, other: Hex) -> Hex {
+        Hex::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl std::ops::Sub for Hex {
+    type Output = Hex;
+
+    fn sub(self, other: Hex) -> Hex {
+        Hex::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl std::ops::Mul<i32> for Hex {
+    type Output = Hex;
+
+    fn mul(self, factor: i32) -> Hex {
+        Hex::new(self.q * factor, self.r * factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::*;
+
+    fn hexes() -> Vec<Hex> {
+        Hex::new(2, -1).spiral(3)
+    }
+
+    #[test]
+    fn offset_round_trips_and_matches_the_grid() {
+        for hex in hexes() {
+            let (col, row) = hex.offset();
+            assert_eq!(Hex::from_offset(col, row), hex);
+        }
+
+        // The tile at column 0 row 0 is the hexagon at the origin, every other one is where its hexagon is centered.
+        let grid = Grid::hex_grid(5, 4, 10_f32, Vec2::ZERO);
+        for tile in &grid.tiles {
+            let hex = tile.hex.unwrap();
+            assert!(tile.position.distance(hex.center(10_f32)) < 1e-3_f32, "{:?} at {}", hex, tile.position);
+        }
+    }
+
+    #[test]
+    fn grid_neighbors_follow_the_directions() {
+        let grid = Grid::hex_grid(5, 4, 10_f32, Vec2::ZERO);
+        for tile in &grid.tiles {
+            let expected : Vec<Hex> = tile.hex.unwrap().neighbors().into_iter().filter(|hex| grid.tiles.iter().any(|other| other.hex == Some(*hex))).collect();
+            let neighbors : Vec<Hex> = tile.neighbors.iter().map(|index| grid.tiles[*index].hex.unwrap()).collect();
+            assert_eq!(neighbors, expected);
+        }
+    }
+
+    #[test]
+    fn distance_is_symmetric() {
+        for a in hexes() {
+            for b in hexes() {
+                assert_eq!(a.distance(b), b.distance(a));
+            }
+        }
+
+        assert_eq!(Hex::new(0, 0).distance(Hex::new(3, -1)), 3);
+    }
+
+    #[test]
+    fn ring_has_six_times_radius_hexes() {
+        let center = Hex::new(1, 2);
+        for radius in 1..5 {
+            let ring = center.ring(radius);
+            assert_eq!(ring.len(), 6 * radius as usize);
+            assert!(ring.iter().all(|hex| hex.distance(center) == radius));
+        }
+    }
+
+    #[test]
+    fn spiral_has_every_hex_up_to_radius() {
+        for radius in 0..5 {
+            let mut spiral = Hex::default().spiral(radius);
+            assert_eq!(spiral.len(), (3 * radius * (radius + 1) + 1) as usize);
+            spiral.sort_by_key(|hex| (hex.q, hex.r));
+            spiral.dedup();
+            assert_eq!(spiral.len(), (3 * radius * (radius + 1) + 1) as usize);
+        }
+    }
+
+    #[test]
+    fn line_goes_from_one_end_to_the_other() {
+        for a in hexes() {
+            for b in hexes() {
+                let line = a.line(b);
+                assert_eq!(line.len(), a.distance(b) as usize + 1);
+                assert_eq!(line.first(), Some(&a));
+                assert_eq!(line.last(), Some(&b));
+                assert!(line.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+            }
+        }
+    }
+
+    #[test]
+    fn six_rotations_are_the_identity() {
+        let center = Hex::new(-1, 3);
+        for hex in hexes() {
+            assert_eq!(hex.rotate(center, 6), hex);
+            assert_eq!(hex.rotate(center, 1).rotate(center, -1), hex);
+            assert_eq!(hex.rotate(center, 1).distance(center), hex.distance(center));
+        }
+    }
+
+    #[test]
+    fn at_finds_the_hex_of_its_center() {
+        for hex in hexes() {
+            assert_eq!(Hex::at(hex.center(7_f32), 7_f32), hex);
+        }
+    }
+}
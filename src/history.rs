@@ -0,0 +1,63 @@
+use crate::drawing::*;
+use crate::generator::*;
+use crate::grid::*;
+
+/// A generated drawing along with what is needed to generate it again.
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub drawing : Drawing,
+    /// Strokes drawn by hand at that time, already part of the drawing.
+    pub sketch : Drawing,
+    pub seed : u64,
+    pub generator : &'static str,
+    pub parameters : Vec<Parameter>,
+    pub grid_parameters : GridParameters,
+    /// What the generator kept of that generation, so regions of the drawing can be walked again.
+    pub generator_cache : GeneratorCache,
+}
+
+/// Linear undo history, recording after an undo drops the entries that could have been redone.
+pub struct History {
+    entries : Vec<HistoryEntry>,
+    current : usize,
+    capacity : usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History { entries: Vec::new(), current: 0, capacity: capacity.max(1) }
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.truncate(self.current + 1);
+        self.entries.push(entry);
+        if self.entries.len() > self.capacity {
+            self.entries.remove(0);
+        }
+
+        self.current = self.entries.len() - 1;
+    }
+
+    pub fn undo(&mut self) -> Option<&HistoryEntry> {
+        if self.current == 0 {
+            return None;
+        }
+
+        self.current -= 1;
+        self.entries.get(self.current)
+    }
+
+    pub fn redo(&mut self) -> Option<&HistoryEntry> {
+        if self.current + 1 >= self.entries.len() {
+            return None;
+        }
+
+        self.current += 1;
+        self.entries.get(self.current)
+    }
+
+    /// Position of the displayed entry, starting at 1, and the number of entries.
+    pub fn position(&self) -> (usize, usize) {
+        (self.current + 1, self.entries.len())
+    }
+}
@@ -0,0 +1,567 @@
+use std::fs;
+use std::str::from_utf8;
+
+use glam::*;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::drawing::*;
+use crate::error::*;
+use crate::utils::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ImportMode {
+    /// Fitted to the page margins and drawn around the generated content.
+    Frame,
+    /// Fitted to the grid and drawn on top of the walk.
+    Overlay,
+    /// Fitted to the grid, only the tiles inside its closed shapes are walked, not drawn.
+    Mask,
+}
+
+impl ImportMode {
+    pub fn parse(name: &str) -> Option<ImportMode> {
+        match name {
+            "frame" => Some(ImportMode::Frame),
+            "overlay" => Some(ImportMode::Overlay),
+            "mask" => Some(ImportMode::Mask),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the visible shapes of a svg file as strokes, transforms applied.
+pub fn import_svg(file_name: &str, attributes: &StrokeAttributes) -> Result<Drawing> {
+    let file_raw = fs::read_to_string(file_name)
+        .map_err(|error| Error::io(file_name, error))?;
+    let mut reader = Reader::from_str(&file_raw);
+
+    let mut drawing = Drawing::new();
+    let mut transforms = vec![Affine2::IDENTITY];
+    // Depth inside elements that are only referenced, never rendered directly.
+    let mut hidden_depth = 0;
+
+    loop {
+        let position = reader.buffer_position();
+        let xml_error = |source| Error::Xml { file_name: file_name.to_owned(), position, source };
+        match reader.read_event_unbuffered() {
+            Ok(Event::Start(ref e)) => {
+                if hidden_depth > 0 || is_hidden(e) {
+                    hidden_depth += 1;
+                    continue;
+                }
+
+                let transform = transforms[transforms.len() - 1] * element_transform(e).map_err(xml_error)?;
+                import_element(e, &transform, attributes, &mut drawing).map_err(xml_error)?;
+                transforms.push(transform);
+            },
+            Ok(Event::Empty(ref e)) => {
+                if hidden_depth > 0 || is_hidden(e) {
+                    continue;
+                }
+
+                let transform = transforms[transforms.len() - 1] * element_transform(e).map_err(xml_error)?;
+                import_element(e, &transform, attributes, &mut drawing).map_err(xml_error)?;
+            },
+            Ok(Event::End(_)) => {
+                if hidden_depth > 0 {
+                    hidden_depth -= 1;
+                }
+                else {
+                    transforms.pop();
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(source) => return Err(Error::Xml { file_name: file_name.to_owned(), position: reader.buffer_position(), source }),
+            _ => (),
+        }
+    }
+
+    Ok(drawing)
+}
+
+fn is_hidden(element: &BytesStart) -> bool {
+    matches!(element.name(), b"defs" | b"clipPath" | b"mask" | b"marker" | b"pattern" | b"symbol")
+}
+
+// Attributes that are not well formed are reported by the caller along with the element position.
+type XmlResult<T> = std::result::Result<T, quick_xml::Error>;
+
+fn attribute(element: &BytesStart, name: &[u8]) -> XmlResult<Option<String>> {
+    for attr in element.attributes() {
+        let attr = attr?;
+        if attr.key == name {
+            return Ok(Some(from_utf8(&attr.value).map_err(quick_xml::Error::Utf8)?.to_owned()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn number_attribute(element: &BytesStart, name: &[u8]) -> XmlResult<f32> {
+    Ok(match attribute(element, name)? {
+        Some(value) => parse_numbers(&value).first().copied().unwrap_or(0_f32),
+        None => 0_f32,
+    })
+}
+
+fn element_transform(element: &BytesStart) -> XmlResult<Affine2> {
+    Ok(match attribute(element, b"transform")? {
+        Some(transform) => parse_transform(&transform),
+        None => Affine2::IDENTITY,
+    })
+}
+
+fn import_element(element: &BytesStart, transform: &Affine2, attributes: &StrokeAttributes, drawing: &mut Drawing) -> XmlResult<()> {
+    let mut imported = Drawing::new();
+    let closed = StrokeAttributes { closed: true, ..attributes.clone() };
+
+    match element.name() {
+        b"path" => {
+            if let Some(data) = attribute(element, b"d")? {
+                parse_path_data(&data, attributes, &mut imported);
+            }
+        },
+        b"polyline" | b"polygon" => {
+            if let Some(points) = attribute(element, b"points")? {
+                let points : Vec<Vec2> = parse_numbers(&points).chunks_exact(2).map(|p| Vec2::new(p[0], p[1])).collect();
+                if points.len() > 1 {
+                    let stroke_attributes = match element.name() {
+                        b"polygon" => closed.clone(),
+                        _ => attributes.clone(),
+                    };
+
+                    imported.extend(Stroke::from_polyline(&points, stroke_attributes));
+                }
+            }
+        },
+        b"line" => {
+            let start = Vec2::new(number_attribute(element, b"x1")?, number_attribute(element, b"y1")?);
+            let end = Vec2::new(number_attribute(element, b"x2")?, number_attribute(element, b"y2")?);
+            imported.extend(Stroke::from_polyline(&[start, end], attributes.clone()));
+        },
+        b"rect" => {
+            let position = Vec2::new(number_attribute(element, b"x")?, number_attribute(element, b"y")?);
+            let size = Vec2::new(number_attribute(element, b"width")?, number_attribute(element, b"height")?);
+            let corners = [position, position + Vec2::new(size.x, 0_f32), position + size, position + Vec2::new(0_f32, size.y)];
+            imported.extend(Stroke::from_polyline(&corners, closed.clone()));
+        },
+        b"circle" | b"ellipse" => {
+            let center = Vec2::new(number_attribute(element, b"cx")?, number_attribute(element, b"cy")?);
+            let radius = match element.name() {
+                b"circle" => Vec2::splat(number_attribute(element, b"r")?),
+                _ => Vec2::new(number_attribute(element, b"rx")?, number_attribute(element, b"ry")?),
+            };
+
+            imported.add(ellipse_stroke(center, radius, &closed));
+        },
+        _ => (),
+    }
+
+    imported.transform(transform);
+    drawing.append(imported);
+    Ok(())
+}
+
+// Four cubic arcs, the usual kappa approximation of a quarter circle.
+fn ellipse_stroke(center: Vec2, radius: Vec2, attributes: &StrokeAttributes) -> Stroke {
+    let kappa = 0.552_284_8_f32;
+    let mut stroke = Stroke::new(center + Vec2::new(radius.x, 0_f32), attributes.clone());
+    let directions = [Vec2::new(1_f32, 0_f32), Vec2::new(0_f32, 1_f32), Vec2::new(-1_f32, 0_f32), Vec2::new(0_f32, -1_f32)];
+    for quarter in 0..4 {
+        let from = directions[quarter] * radius;
+        let to = directions[(quarter + 1) % 4] * radius;
+        stroke.cubic_to(center + from + to * kappa, center + to + from * kappa, center + to);
+    }
+
+    stroke
+}
+
+pub fn parse_numbers(value: &str) -> Vec<f32> {
+    let mut numbers = Vec::new();
+    let mut tokenizer = Tokenizer::new(value);
+    while let Some(number) = tokenizer.number() {
+        numbers.push(number);
+    }
+
+    numbers
+}
+
+pub fn parse_transform(value: &str) -> Affine2 {
+    let mut transform = Affine2::IDENTITY;
+    for part in value.split(')') {
+        let mut split = part.split('(');
+        let name = split.next().unwrap_or("").trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let arguments = parse_numbers(split.next().unwrap_or(""));
+        let argument = |index: usize, default: f32| arguments.get(index).copied().unwrap_or(default);
+
+        transform = transform * match name {
+            "matrix" if arguments.len() == 6 => Affine2::from_cols_array(&[arguments[0], arguments[1], arguments[2], arguments[3], arguments[4], arguments[5]]),
+            "translate" => Affine2::from_translation(Vec2::new(argument(0, 0_f32), argument(1, 0_f32))),
+            "scale" => Affine2::from_scale(Vec2::new(argument(0, 1_f32), argument(1, argument(0, 1_f32)))),
+            "rotate" => {
+                let origin = Vec2::new(argument(1, 0_f32), argument(2, 0_f32));
+                Affine2::from_translation(origin) * Affine2::from_angle(argument(0, 0_f32).to_radians()) * Affine2::from_translation(-origin)
+            },
+            "skewX" => Affine2::from_cols_array(&[1_f32, 0_f32, argument(0, 0_f32).to_radians().tan(), 1_f32, 0_f32, 0_f32]),
+            "skewY" => Affine2::from_cols_array(&[1_f32, argument(0, 0_f32).to_radians().tan(), 0_f32, 1_f32, 0_f32, 0_f32]),
+            _ => Affine2::IDENTITY,
+        };
+    }
+
+    transform
+}
+
+struct Tokenizer<'a> {
+    chars : std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(value: &'a str) -> Tokenizer<'a> {
+        Tokenizer { chars: value.chars().peekable() }
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() || *c == ',' {
+                self.chars.next();
+            }
+            else {
+                break;
+            }
+        }
+    }
+
+    fn command(&mut self) -> Option<char> {
+        self.skip_separators();
+        match self.chars.peek() {
+            Some(c) if c.is_ascii_alphabetic() && *c != 'e' && *c != 'E' => self.chars.next(),
+            _ => None,
+        }
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.chars.peek().is_none()
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let mut number = String::new();
+        let mut has_dot = false;
+        let mut has_exponent = false;
+
+        while let Some(&c) = self.chars.peek() {
+            let previous = number.chars().last();
+            let accepted = match c {
+                '0'..='9' => true,
+                '-' | '+' => number.is_empty() || previous == Some('e') || previous == Some('E'),
+                '.' => !has_dot && !has_exponent,
+                'e' | 'E' => !has_exponent && !number.is_empty(),
+                _ => false,
+            };
+
+            if !accepted {
+                break;
+            }
+
+            has_dot = has_dot || c == '.';
+            has_exponent = has_exponent || c == 'e' || c == 'E';
+            number.push(c);
+            self.chars.next();
+        }
+
+        number.parse::<f32>().ok()
+    }
+
+    // Arc flags may be written without any separator, as in `a1 1 0 01 1 1`.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        match self.chars.next() {
+            Some('0') => Some(false),
+            Some('1') => Some(true),
+            _ => None,
+        }
+    }
+
+    fn point(&mut self) -> Option<Vec2> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Some(Vec2::new(x, y))
+    }
+}
+
+/// Adds the strokes of the `d` attribute of a svg path.
+pub fn parse_path_data(data: &str, attributes: &StrokeAttributes, drawing: &mut Drawing) {
+    let mut tokenizer = Tokenizer::new(data);
+    let mut stroke : Option<Stroke> = None;
+    let mut current = Vec2::ZERO;
+    let mut subpath_start = Vec2::ZERO;
+    let mut last_cubic_control : Option<Vec2> = None;
+    let mut last_quad_control : Option<Vec2> = None;
+    let mut command = 'M';
+
+    let flush = |stroke : &mut Option<Stroke>, closed : bool, drawing : &mut Drawing| {
+        if let Some(mut finished) = stroke.take() {
+            if finished.segment_count() > 0 {
+                finished.attributes.closed = closed;
+                drawing.add(finished);
+            }
+        }
+    };
+
+    while !tokenizer.is_at_end() {
+        match tokenizer.command() {
+            Some(next) => command = next,
+            // Nothing may follow a close without a new command.
+            None if command.eq_ignore_ascii_case(&'z') => break,
+            None => (),
+        }
+
+        let relative = command.is_ascii_lowercase();
+        let origin = match relative {
+            true => current,
+            false => Vec2::ZERO,
+        };
+
+        // Smooth curves only reflect the control point of a curve of the same kind.
+        let previous_cubic_control = last_cubic_control.take();
+        let previous_quad_control = last_quad_control.take();
+        match command.to_ascii_uppercase() {
+            'M' => {
+                let Some(point) = tokenizer.point() else { break };
+                flush(&mut stroke, false, drawing);
+                current = origin + point;
+                subpath_start = current;
+                stroke = Some(Stroke::new(current, attributes.clone()));
+                // Coordinates following a move are implicit lines.
+                command = match relative {
+                    true => 'l',
+                    false => 'L',
+                };
+            },
+            'Z' => {
+                flush(&mut stroke, true, drawing);
+                current = subpath_start;
+            },
+            'L' | 'H' | 'V' => {
+                let point = match command.to_ascii_uppercase() {
+                    'H' => tokenizer.number().map(|x| Vec2::new(origin.x + x, current.y)),
+                    'V' => tokenizer.number().map(|y| Vec2::new(current.x, origin.y + y)),
+                    _ => tokenizer.point().map(|point| origin + point),
+                };
+
+                let Some(point) = point else { break };
+                stroke.get_or_insert_with(|| Stroke::new(current, attributes.clone())).line_to(point);
+                current = point;
+            },
+            'C' | 'S' => {
+                let control1 = match command.to_ascii_uppercase() {
+                    'C' => tokenizer.point().map(|point| origin + point),
+                    _ => Some(previous_cubic_control.map(|control| current * 2_f32 - control).unwrap_or(current)),
+                };
+
+                let (Some(control1), Some(control2), Some(point)) = (control1, tokenizer.point(), tokenizer.point()) else { break };
+                let control2 = origin + control2;
+                let point = origin + point;
+                stroke.get_or_insert_with(|| Stroke::new(current, attributes.clone())).cubic_to(control1, control2, point);
+                last_cubic_control = Some(control2);
+                current = point;
+            },
+            'Q' | 'T' => {
+                let control = match command.to_ascii_uppercase() {
+                    'Q' => tokenizer.point().map(|point| origin + point),
+                    _ => Some(previous_quad_control.map(|control| current * 2_f32 - control).unwrap_or(current)),
+                };
+
+                let (Some(control), Some(point)) = (control, tokenizer.point()) else { break };
+                let point = origin + point;
+                stroke.get_or_insert_with(|| Stroke::new(current, attributes.clone())).quad_to(control, point);
+                last_quad_control = Some(control);
+                current = point;
+            },
+            'A' => {
+                let (Some(rx), Some(ry), Some(rotation), Some(large_arc), Some(sweep), Some(point)) = (tokenizer.number(), tokenizer.number(), tokenizer.number(), tokenizer.flag(), tokenizer.flag(), tokenizer.point()) else { break };
+                let point = origin + point;
+                let stroke = stroke.get_or_insert_with(|| Stroke::new(current, attributes.clone()));
+                arc_to(stroke, current, Vec2::new(rx, ry), rotation.to_radians(), large_arc, sweep, point);
+                current = point;
+            },
+            _ => break,
+        }
+    }
+
+    flush(&mut stroke, false, drawing);
+}
+
+// Endpoint to center parameterization from the SVG implementation notes, split in cubic arcs of at most a quarter turn.
+fn arc_to(stroke: &mut Stroke, from: Vec2, radius: Vec2, rotation: f32, large_arc: bool, sweep: bool, to: Vec2) {
+    let mut radius = radius.abs();
+    if radius.x < f32::EPSILON || radius.y < f32::EPSILON || from.distance(to) < f32::EPSILON {
+        stroke.line_to(to);
+        return;
+    }
+
+    let (sin, cos) = rotation.sin_cos();
+    let half = (from - to) / 2_f32;
+    let p = Vec2::new(cos * half.x + sin * half.y, -sin * half.x + cos * half.y);
+
+    let lambda = (p.x * p.x) / (radius.x * radius.x) + (p.y * p.y) / (radius.y * radius.y);
+    if lambda > 1_f32 {
+        radius *= lambda.sqrt();
+    }
+
+    let numerator = radius.x * radius.x * radius.y * radius.y - radius.x * radius.x * p.y * p.y - radius.y * radius.y * p.x * p.x;
+    let denominator = radius.x * radius.x * p.y * p.y + radius.y * radius.y * p.x * p.x;
+    let mut factor = (numerator / denominator).max(0_f32).sqrt();
+    if large_arc == sweep {
+        factor = -factor;
+    }
+
+    let center_prime = Vec2::new(factor * radius.x * p.y / radius.y, -factor * radius.y * p.x / radius.x);
+    let center = Vec2::new(cos * center_prime.x - sin * center_prime.y, sin * center_prime.x + cos * center_prime.y) + (from + to) / 2_f32;
+
+    let start_vector = (p - center_prime) / radius;
+    let end_vector = (-p - center_prime) / radius;
+    let start_angle = start_vector.y.atan2(start_vector.x);
+    let mut delta = end_vector.y.atan2(end_vector.x) - start_angle;
+    if sweep && delta < 0_f32 {
+        delta += std::f32::consts::TAU;
+    }
+    else if !sweep && delta > 0_f32 {
+        delta -= std::f32::consts::TAU;
+    }
+
+    let point = |angle: f32| {
+        let local = Vec2::new(angle.cos(), angle.sin()) * radius;
+        center + Vec2::new(cos * local.x - sin * local.y, sin * local.x + cos * local.y)
+    };
+
+    let derivative = |angle: f32| {
+        let local = Vec2::new(-angle.sin(), angle.cos()) * radius;
+        Vec2::new(cos * local.x - sin * local.y, sin * local.x + cos * local.y)
+    };
+
+    let nb_segments = (delta.abs() / std::f32::consts::FRAC_PI_2).ceil().max(1_f32) as usize;
+    let step = delta / nb_segments as f32;
+    let tangent_length = 4_f32 / 3_f32 * (step / 4_f32).tan();
+    for segment in 0..nb_segments {
+        let angle1 = start_angle + step * segment as f32;
+        let angle2 = angle1 + step;
+        let end = match segment == nb_segments - 1 {
+            true => to,
+            false => point(angle2),
+        };
+
+        stroke.cubic_to(point(angle1) + derivative(angle1) * tangent_length, end - derivative(angle2) * tangent_length, end);
+    }
+}
+
+/// Uniformly scales and centers the drawing so it fits inside `target`.
+pub fn fit_drawing(drawing: &mut Drawing, target: &Bounds) {
+    let Some(bounds) = drawing.bounds() else { return };
+    let size = bounds.size();
+    let target_size = target.size();
+    let scale = (target_size.x / size.x.max(f32::EPSILON)).min(target_size.y / size.y.max(f32::EPSILON));
+
+    drawing.translate(-bounds.center());
+    drawing.scale(Vec2::splat(scale), Vec2::ZERO);
+    drawing.translate(target.center());
+}
+
+/// Even-odd rule against every closed stroke of the drawing.
+pub fn is_inside(drawing: &Drawing, point: Vec2) -> bool {
+    drawing.iter()
+        .filter(|stroke| stroke.attributes.closed)
+        .filter(|stroke| polygon_contains(&stroke.flattened_points(8), point))
+        .count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn end_points(stroke: &Stroke) -> Vec<Vec2> {
+        stroke.instructions().iter().map(Instruction::end_point).collect()
+    }
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-3_f32, "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn path_commands_are_relative_or_absolute() {
+        let mut drawing = Drawing::new();
+        parse_path_data("M 10 10 l 5 0 v 5 H 0 z m 1 1 L 2 2 c 1 0 2 1 2 2 S 4 6 5 5", &StrokeAttributes::default(), &mut drawing);
+        let strokes : Vec<&Stroke> = drawing.iter().collect();
+        assert_eq!(strokes.len(), 2);
+
+        assert!(strokes[0].attributes.closed);
+        assert_eq!(end_points(strokes[0]), vec![Vec2::new(10_f32, 10_f32), Vec2::new(15_f32, 10_f32), Vec2::new(15_f32, 15_f32), Vec2::new(0_f32, 15_f32)]);
+
+        // The second subpath starts relative to where the closed one started.
+        assert!(!strokes[1].attributes.closed);
+        assert_eq!(end_points(strokes[1]), vec![Vec2::new(11_f32, 11_f32), Vec2::new(2_f32, 2_f32), Vec2::new(4_f32, 4_f32), Vec2::new(5_f32, 5_f32)]);
+        match strokes[1].instructions()[3] {
+            // The smooth curve reflects the second control point of the relative curve around (4, 4).
+            Instruction::CubicTo(control1, control2, _) => {
+                assert_eq!(control1, Vec2::new(4_f32, 5_f32));
+                assert_eq!(control2, Vec2::new(4_f32, 6_f32));
+            },
+            _ => panic!("expected a cubic"),
+        }
+    }
+
+    #[test]
+    fn elliptical_arcs_become_quarter_cubics() {
+        let mut drawing = Drawing::new();
+        parse_path_data("M 0 0 A 10 10 0 0 1 20 0", &StrokeAttributes::default(), &mut drawing);
+        let stroke = drawing.iter().next().unwrap();
+        let center = Vec2::new(10_f32, 0_f32);
+
+        let mut start = stroke.start();
+        assert_eq!(stroke.instructions().len(), 3);
+        for instruction in &stroke.instructions()[1..] {
+            let Instruction::CubicTo(control1, control2, end) = *instruction else { panic!("expected a cubic") };
+            for step in 0..=8 {
+                let point = cubic_point(start, control1, control2, end, step as f32 / 8_f32);
+                assert!((point.distance(center) - 10_f32).abs() < 0.05_f32, "{} is off the circle", point);
+                assert!(point.y <= 1e-3_f32, "{} is on the wrong side", point);
+            }
+
+            start = end;
+        }
+
+        assert_eq!(start, Vec2::new(20_f32, 0_f32));
+    }
+
+    #[test]
+    fn transforms_chain_and_nest() {
+        let transform = parse_transform("translate(10, 20) scale(2) rotate(90)");
+        assert_near(transform.transform_point2(Vec2::new(1_f32, 0_f32)), Vec2::new(10_f32, 22_f32));
+        assert_near(parse_transform("matrix(1 0 0 1 3 4)").transform_point2(Vec2::ZERO), Vec2::new(3_f32, 4_f32));
+        assert_near(parse_transform("rotate(180, 5, 5)").transform_point2(Vec2::ZERO), Vec2::new(10_f32, 10_f32));
+
+        let file_name = std::env::temp_dir().join(format!("svg_experiments_nested_{}.svg", std::process::id()));
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <defs><line x1="0" y1="0" x2="100" y2="100"/></defs>
+            <g transform="translate(10,20)">
+                <g transform="scale(2)">
+                    <line x1="0" y1="0" x2="1" y2="1"/>
+                </g>
+                <line x1="0" y1="0" x2="1" y2="0"/>
+            </g>
+        </svg>"#;
+        fs::write(&file_name, svg).unwrap();
+        let drawing = import_svg(file_name.to_str().unwrap(), &StrokeAttributes::default());
+        fs::remove_file(&file_name).unwrap();
+
+        let drawing = drawing.unwrap();
+        let strokes : Vec<Vec<Vec2>> = drawing.iter().map(end_points).collect();
+        assert_eq!(strokes.len(), 2);
+        assert_near(strokes[0][0], Vec2::new(10_f32, 20_f32));
+        assert_near(strokes[0][1], Vec2::new(12_f32, 22_f32));
+        assert_near(strokes[1][0], Vec2::new(10_f32, 20_f32));
+        assert_near(strokes[1][1], Vec2::new(11_f32, 20_f32));
+    }
+}
@@ -0,0 +1,39 @@
+//! Generative line drawings for pen plotters.
+//!
+//! A drawing is made of strokes of move, line and curve instructions, in drawing units that are millimetres times a scale.
+//! [`grid::Grid`] builds hexagonal and square grids, random walks and [`truchet`] tiles over them, [`generator`] turns those into drawings,
+//! [`font::Font`] lays out text with single line svg fonts, [`simplify`], [`hatching`] and [`drawing`] operate on paths
+//! and [`export`] writes the result as an svg file ready to plot.
+//!
+//! The ggez preview window lives behind the `viewer` feature and loading density pictures behind the `raster` feature,
+//! without them the crate only depends on glam, svg, rand and quick-xml.
+
+pub mod error;
+pub mod utils;
+pub mod drawing;
+pub mod font;
+pub mod signature;
+pub mod simplify;
+pub mod import;
+pub mod export;
+pub mod batch;
+pub mod analysis;
+pub mod hatching;
+pub mod density;
+pub mod noise;
+pub mod flow_field;
+pub(crate) mod config;
+pub mod generator;
+#[cfg(feature = "viewer")]
+pub(crate) mod history;
+pub mod plotter;
+pub mod sketch;
+pub mod hex;
+pub mod grid;
+pub mod truchet;
+pub mod composer;
+#[cfg(feature = "viewer")]
+pub(crate) mod ui;
+#[cfg(feature = "viewer")]
+pub(crate) mod application;
+pub mod cli;
@@ -0,0 +1,65 @@
+use glam::*;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+/// Classic 2D Perlin gradient noise over a shuffled permutation table.
+pub struct Perlin {
+    permutation : [u8; 512],
+}
+
+impl Perlin {
+    pub fn new<R: Rng>(random: &mut R) -> Perlin {
+        let mut table : Vec<u8> = (0..=255).collect();
+        table.shuffle(random);
+
+        let mut permutation = [0_u8; 512];
+        for index in 0..512 {
+            permutation[index] = table[index % 256];
+        }
+
+        Perlin { permutation }
+    }
+
+    fn gradient(&self, x: i32, y: i32, offset: Vec2) -> f32 {
+        let hash = self.permutation[self.permutation[(x & 255) as usize] as usize + (y & 255) as usize];
+        let angle = hash as f32 / 256_f32 * std::f32::consts::TAU;
+        Vec2::new(angle.cos(), angle.sin()).dot(offset)
+    }
+
+    /// Roughly in [-1, 1].
+    pub fn noise(&self, position: Vec2) -> f32 {
+        let cell = position.floor();
+        let local = position - cell;
+        let x = cell.x as i32;
+        let y = cell.y as i32;
+
+        let fade = |t: f32| t * t * t * (t * (t * 6_f32 - 15_f32) + 10_f32);
+        let u = fade(local.x);
+        let v = fade(local.y);
+
+        let n00 = self.gradient(x, y, local);
+        let n10 = self.gradient(x + 1, y, local - Vec2::new(1_f32, 0_f32));
+        let n01 = self.gradient(x, y + 1, local - Vec2::new(0_f32, 1_f32));
+        let n11 = self.gradient(x + 1, y + 1, local - Vec2::new(1_f32, 1_f32));
+
+        let bottom = n00 + (n10 - n00) * u;
+        let top = n01 + (n11 - n01) * u;
+        (bottom + (top - bottom) * v) * std::f32::consts::SQRT_2
+    }
+
+    /// Sum of `octaves` noises, each one twice the frequency and half the amplitude of the previous.
+    pub fn fractal(&self, position: Vec2, octaves: usize) -> f32 {
+        let mut value = 0_f32;
+        let mut amplitude = 1_f32;
+        let mut frequency = 1_f32;
+        let mut total = 0_f32;
+        for octave in 0..octaves.max(1) {
+            value += self.noise(position * frequency + Vec2::splat(octave as f32 * 17.31_f32)) * amplitude;
+            total += amplitude;
+            amplitude /= 2_f32;
+            frequency *= 2_f32;
+        }
+
+        value / total
+    }
+}
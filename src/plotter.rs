@@ -0,0 +1,260 @@
+use glam::*;
+
+use crate::drawing::*;
+use crate::export::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct PlotterParameters {
+    /// Speeds in millimetres per second.
+    pub pen_down_speed : f32,
+    pub pen_up_speed : f32,
+    /// In millimetres per second squared, the same for speeding up and slowing down.
+    pub acceleration : f32,
+    /// Seconds taken to raise or to lower the pen.
+    pub pen_lift_duration : f32,
+}
+
+impl Default for PlotterParameters {
+    fn default() -> PlotterParameters {
+        PlotterParameters {
+            pen_down_speed: 25_f32,
+            pen_up_speed: 75_f32,
+            acceleration: 200_f32,
+            pen_lift_duration: 0.15_f32,
+        }
+    }
+}
+
+/// A straight move of the pen, following a trapezoidal speed profile.
+#[derive(Copy, Clone, Debug)]
+pub struct PlotSegment {
+    pub from : Vec2,
+    pub to : Vec2,
+    pub pen_down : bool,
+    /// Seconds since the start of the plot.
+    pub start : f32,
+    pub duration : f32,
+    // Length in millimetres, the positions being in drawing units.
+    length : f32,
+    entry_speed : f32,
+    cruise_speed : f32,
+    acceleration : f32,
+    acceleration_duration : f32,
+    cruise_duration : f32,
+}
+
+impl PlotSegment {
+    fn new(from: Vec2, to: Vec2, pen_down: bool, length: f32, (entry_speed, exit_speed): (f32, f32), max_speed: f32, acceleration: f32) -> PlotSegment {
+        // Highest speed reachable when speeding up then slowing down right away.
+        let peak_speed = ((2_f32 * acceleration * length + entry_speed * entry_speed + exit_speed * exit_speed) / 2_f32).sqrt();
+        let cruise_speed = peak_speed.min(max_speed).max(entry_speed).max(exit_speed);
+        let acceleration_duration = (cruise_speed - entry_speed) / acceleration;
+        let deceleration_duration = (cruise_speed - exit_speed) / acceleration;
+        let acceleration_length = (entry_speed + cruise_speed) / 2_f32 * acceleration_duration;
+        let deceleration_length = (exit_speed + cruise_speed) / 2_f32 * deceleration_duration;
+        let cruise_duration = match cruise_speed > 0_f32 {
+            true => (length - acceleration_length - deceleration_length).max(0_f32) / cruise_speed,
+            false => 0_f32,
+        };
+
+        PlotSegment {
+            from,
+            to,
+            pen_down,
+            start: 0_f32,
+            duration: acceleration_duration + cruise_duration + deceleration_duration,
+            length,
+            entry_speed,
+            cruise_speed,
+            acceleration,
+            acceleration_duration,
+            cruise_duration,
+        }
+    }
+
+    pub fn end(&self) -> f32 {
+        self.start + self.duration
+    }
+
+    /// Position of the pen `time` seconds after the start of the plot.
+    pub fn position_at(&self, time: f32) -> Vec2 {
+        let elapsed = (time - self.start).clamp(0_f32, self.duration);
+        let accelerated = self.acceleration_duration.min(elapsed);
+        let mut distance = self.entry_speed * accelerated + self.acceleration * accelerated * accelerated / 2_f32;
+        distance += self.cruise_speed * (elapsed - self.acceleration_duration).clamp(0_f32, self.cruise_duration);
+
+        let decelerated = (elapsed - self.acceleration_duration - self.cruise_duration).max(0_f32);
+        distance += self.cruise_speed * decelerated - self.acceleration * decelerated * decelerated / 2_f32;
+
+        match self.length > 0_f32 {
+            true => self.from.lerp(self.to, (distance / self.length).clamp(0_f32, 1_f32)),
+            false => self.to,
+        }
+    }
+}
+
+/// Timed replay of the export of a drawing as a pen plotter would draw it, starting and ending at the origin.
+pub struct PlotSimulation {
+    pub segments : Vec<PlotSegment>,
+    pub duration : f32,
+    pub pen_lifts : usize,
+    /// In millimetres.
+    pub pen_down_length : f32,
+    pub pen_up_length : f32,
+}
+
+impl PlotSimulation {
+    /// `scale` converts drawing units into millimetres, lines the export skips are not plotted.
+    pub fn new(drawing: &Drawing, scale: f32, parameters: &PlotterParameters) -> PlotSimulation {
+        PlotSimulation::from_exported(&exported_drawing(drawing), scale, parameters)
+    }
+
+    /// Same as `new` for a drawing already given by `exported_drawing`, which is plotted as it is.
+    pub fn from_exported(exported: &Drawing, scale: f32, parameters: &PlotterParameters) -> PlotSimulation {
+        let mut simulation = PlotSimulation { segments: Vec::new(), duration: 0_f32, pen_lifts: 0, pen_down_length: 0_f32, pen_up_length: 0_f32 };
+        let acceleration = parameters.acceleration.max(1_f32);
+        let mut position = Vec2::ZERO;
+        for stroke in exported.iter() {
+            let points = stroke.flattened_points(8);
+            if points.len() < 2 {
+                continue;
+            }
+
+            simulation.travel(position, points[0], scale, parameters);
+            simulation.path(&points, scale, parameters.pen_down_speed, acceleration);
+            position = points[points.len() - 1];
+        }
+
+        simulation.travel(position, Vec2::ZERO, scale, parameters);
+        simulation
+    }
+
+    fn push(&mut self, mut segment: PlotSegment) {
+        segment.start = self.duration;
+        self.duration = segment.end();
+        self.segments.push(segment);
+    }
+
+    // Pen-up move, the pen being raised before and lowered after unless it is back home.
+    fn travel(&mut self, from: Vec2, to: Vec2, scale: f32, parameters: &PlotterParameters) {
+        let length = from.distance(to) / scale;
+        if length < f32::EPSILON {
+            return;
+        }
+
+        let is_first = self.segments.is_empty();
+        let is_home = to == Vec2::ZERO;
+        if !is_first {
+            self.duration += parameters.pen_lift_duration;
+            self.pen_lifts += 1;
+        }
+
+        self.pen_up_length += length;
+        self.push(PlotSegment::new(from, to, false, length, (0_f32, 0_f32), parameters.pen_up_speed, parameters.acceleration.max(1_f32)));
+        if !is_home {
+            self.duration += parameters.pen_lift_duration;
+        }
+    }
+
+    // Pen-down polyline, slowing down in corners as much as the angle requires and stopping at both ends.
+    fn path(&mut self, points: &[Vec2], scale: f32, max_speed: f32, acceleration: f32) {
+        let lengths : Vec<f32> = points.windows(2).map(|pair| pair[0].distance(pair[1]) / scale).collect();
+        let mut speeds = vec![0_f32; points.len()];
+        for index in 1..points.len() - 1 {
+            let incoming = (points[index] - points[index - 1]).normalize_or_zero();
+            let outgoing = (points[index + 1] - points[index]).normalize_or_zero();
+            speeds[index] = max_speed * (1_f32 + incoming.dot(outgoing)) / 2_f32;
+        }
+
+        // Forward then backward so no segment asks for more acceleration than available.
+        for index in 1..points.len() {
+            speeds[index] = speeds[index].min((speeds[index - 1] * speeds[index - 1] + 2_f32 * acceleration * lengths[index - 1]).sqrt());
+        }
+
+        for index in (0..points.len() - 1).rev() {
+            speeds[index] = speeds[index].min((speeds[index + 1] * speeds[index + 1] + 2_f32 * acceleration * lengths[index]).sqrt());
+        }
+
+        for index in 0..lengths.len() {
+            self.pen_down_length += lengths[index];
+            self.push(PlotSegment::new(points[index], points[index + 1], true, lengths[index], (speeds[index], speeds[index + 1]), max_speed, acceleration));
+        }
+    }
+
+    /// Number of segments started at `time`, the last one possibly being in progress.
+    pub fn started_segments(&self, time: f32) -> usize {
+        self.segments.partition_point(|segment| segment.start <= time)
+    }
+}
+
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..=59 => format!("{} s", seconds),
+        60..=3599 => format!("{} min {:02} s", seconds / 60, seconds % 60),
+        _ => format!("{} h {:02} min {:02} s", seconds / 3600, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(length: f32) -> PlotSegment {
+        let parameters = PlotterParameters::default();
+        PlotSegment::new(Vec2::ZERO, Vec2::new(length, 0_f32), true, length, (0_f32, 0_f32), parameters.pen_down_speed, parameters.acceleration)
+    }
+
+    #[test]
+    fn short_segments_never_reach_full_speed() {
+        let parameters = PlotterParameters::default();
+        let segment = segment(1_f32);
+        let peak_speed = (parameters.acceleration * 1_f32).sqrt();
+        assert!(peak_speed < parameters.pen_down_speed);
+        assert!((segment.cruise_speed - peak_speed).abs() < 1e-4_f32);
+        assert_eq!(segment.cruise_duration, 0_f32);
+        assert!((segment.duration - 2_f32 * peak_speed / parameters.acceleration).abs() < 1e-4_f32);
+
+        // Speeding up then slowing down right away is symmetric.
+        assert!(segment.position_at(segment.duration / 2_f32).distance(Vec2::new(0.5_f32, 0_f32)) < 1e-4_f32);
+        assert_eq!(segment.position_at(segment.duration), Vec2::new(1_f32, 0_f32));
+    }
+
+    #[test]
+    fn long_segments_cruise_at_full_speed() {
+        let parameters = PlotterParameters::default();
+        let segment = segment(100_f32);
+        let acceleration_duration = parameters.pen_down_speed / parameters.acceleration;
+        let acceleration_length = parameters.pen_down_speed * acceleration_duration / 2_f32;
+        let cruise_duration = (100_f32 - 2_f32 * acceleration_length) / parameters.pen_down_speed;
+        assert_eq!(segment.cruise_speed, parameters.pen_down_speed);
+        assert!((segment.acceleration_duration - acceleration_duration).abs() < 1e-4_f32);
+        assert!((segment.cruise_duration - cruise_duration).abs() < 1e-4_f32);
+        assert!((segment.duration - 2_f32 * acceleration_duration - cruise_duration).abs() < 1e-4_f32);
+
+        assert!(segment.position_at(acceleration_duration).distance(Vec2::new(acceleration_length, 0_f32)) < 1e-3_f32);
+        assert!(segment.position_at(segment.duration / 2_f32).distance(Vec2::new(50_f32, 0_f32)) < 1e-3_f32);
+        assert_eq!(segment.position_at(segment.duration), Vec2::new(100_f32, 0_f32));
+    }
+
+    #[test]
+    fn pen_is_lifted_after_each_stroke() {
+        let mut drawing = Drawing::new();
+        for index in 0..3 {
+            let start = Vec2::new(10_f32 + index as f32 * 20_f32, 10_f32);
+            drawing.extend(Stroke::from_polyline(&[start, start + Vec2::new(10_f32, 0_f32), start + Vec2::new(10_f32, 10_f32)], StrokeAttributes::default()));
+        }
+
+        let parameters = PlotterParameters::default();
+        let simulation = PlotSimulation::new(&drawing, 1_f32, &parameters);
+        // Lifted between strokes and before going home, lowered before each stroke.
+        assert_eq!(simulation.pen_lifts, 3);
+        assert_eq!(simulation.segments.iter().filter(|segment| !segment.pen_down).count(), 4);
+        assert_eq!(simulation.segments.iter().filter(|segment| segment.pen_down).count(), 6);
+        assert!((simulation.pen_down_length - 60_f32).abs() < 1e-3_f32);
+
+        let moving : f32 = simulation.segments.iter().map(|segment| segment.duration).sum();
+        assert!((simulation.duration - moving - 6_f32 * parameters.pen_lift_duration).abs() < 1e-3_f32);
+        assert_eq!(simulation.segments.last().map(|segment| segment.to), Some(Vec2::ZERO));
+    }
+}
@@ -1,21 +1,24 @@
 use std::fs;
 
-pub fn get_signature_counter() -> String {
-    let counter_name = "Medias/counter";
-    let counter_raw = match fs::read_to_string(counter_name) {Ok(result)=>result, _=>panic!("could not read the counter file {}", counter_name)};
-    let counter = match counter_raw.parse::<i32>() { Ok(result)=> result, _=>panic!("couldn't parse counter {}", counter_raw)};
-    let mut formated_count = format!("{:#03}", counter);
-    return formated_count;
+use crate::error::*;
+
+const COUNTER_NAME : &str = "Medias/counter";
+
+fn read_signature_counter() -> Result<i32> {
+    let counter_raw = fs::read_to_string(COUNTER_NAME).map_err(|error| Error::io(COUNTER_NAME, error))?;
+    counter_raw.trim().parse::<i32>().map_err(|_| Error::Counter { file_name: COUNTER_NAME.to_owned(), content: counter_raw })
 }
 
-pub fn get_signature() -> String{
-    format!("AntonMakesGames {}", get_signature_counter())
+/// Number of the next exported drawing, padded to three digits.
+pub fn get_signature_counter() -> Result<String> {
+    Ok(format!("{:#03}", read_signature_counter()?))
 }
 
-pub fn increment_signature_counter() -> std::io::Result<()> {
-    let counter_name = "Medias/counter";
-    let counter_raw = match fs::read_to_string(counter_name) {Ok(result)=>result, _=>panic!("could not read the counter file {}", counter_name)};
-    let counter = match counter_raw.parse::<i32>() { Ok(result)=> result, _=>panic!("couldn't parse counter {}", counter_raw)};
-    fs::write(counter_name, format!("{}", counter + 1))?;
-    Ok(())
-}
\ No newline at end of file
+pub fn get_signature() -> Result<String> {
+    Ok(format!("AntonMakesGames {}", get_signature_counter()?))
+}
+
+pub fn increment_signature_counter() -> Result<()> {
+    let counter = read_signature_counter()?;
+    fs::write(COUNTER_NAME, format!("{}", counter + 1)).map_err(|error| Error::io(COUNTER_NAME, error))
+}
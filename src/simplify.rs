@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use glam::Vec2;
+
+use crate::utils::*;
+use crate::drawing::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SimplificationMethod {
+    RamerDouglasPeucker,
+    VisvalingamWhyatt,
+}
+
+#[derive(Copy, Clone)]
+pub struct SimplificationParameters {
+    pub method : SimplificationMethod,
+    /// Maximum deviation in millimetres, squared to get the minimum triangle area for Visvalingam-Whyatt.
+    pub tolerance : f32,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct SimplificationStatistics {
+    pub points_before : usize,
+    pub points_after : usize,
+}
+
+impl SimplificationParameters {
+    /// Tolerance in millimetres used when simplification is turned on without one.
+    pub const DEFAULT_TOLERANCE : f32 = 0.05_f32;
+}
+
+impl SimplificationStatistics {
+    pub fn points_removed(&self) -> usize {
+        self.points_before - self.points_after
+    }
+}
+
+impl fmt::Display for SimplificationStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ratio = match self.points_before {
+            0 => 0_f32,
+            before => self.points_removed() as f32 / before as f32 * 100_f32,
+        };
+
+        write!(f, "{} / {} points removed ({:.1}%)", self.points_removed(), self.points_before, ratio)
+    }
+}
+
+/// Keeps the points further than `tolerance` from the simplified polyline.
+pub fn ramer_douglas_peucker(positions : &[Vec2], tolerance : f32) -> Vec<Vec2> {
+    if positions.len() < 3 {
+        return positions.to_vec();
+    }
+
+    let mut keep = vec![false; positions.len()];
+    keep[0] = true;
+    keep[positions.len() - 1] = true;
+
+    let mut ranges = vec![(0, positions.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let mut max_distance = 0_f32;
+        let mut max_index = start;
+        for (index, position) in positions.iter().enumerate().take(end).skip(start + 1) {
+            let distance = segment_distance(*position, positions[start], positions[end]);
+            if distance > max_distance {
+                max_distance = distance;
+                max_index = index;
+            }
+        }
+
+        if max_distance > tolerance {
+            keep[max_index] = true;
+            ranges.push((start, max_index));
+            ranges.push((max_index, end));
+        }
+    }
+
+    positions.iter().zip(keep).filter(|(_, keep)| *keep).map(|(position, _)| *position).collect()
+}
+
+struct Triangle {
+    area : f32,
+    index : usize,
+    version : usize,
+}
+
+impl PartialEq for Triangle {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Triangle {}
+
+impl PartialOrd for Triangle {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Triangle {
+    // Reversed so the binary heap pops the smallest area first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.area.total_cmp(&self.area).then(other.index.cmp(&self.index))
+    }
+}
+
+/// Removes the points forming the smallest triangles until every triangle left is larger than `tolerance` squared.
+pub fn visvalingam_whyatt(positions : &[Vec2], tolerance : f32) -> Vec<Vec2> {
+    if positions.len() < 3 {
+        return positions.to_vec();
+    }
+
+    let count = positions.len();
+    let min_area = tolerance * tolerance;
+    let area = |previous: usize, index: usize, next: usize| (positions[index] - positions[previous]).perp_dot(positions[next] - positions[previous]).abs() / 2_f32;
+
+    let mut previous : Vec<usize> = (0..count).map(|i| i.saturating_sub(1)).collect();
+    let mut next : Vec<usize> = (0..count).map(|i| (i + 1).min(count - 1)).collect();
+    let mut versions = vec![0_usize; count];
+    let mut removed = vec![false; count];
+
+    let mut heap = BinaryHeap::new();
+    for index in 1..(count - 1) {
+        heap.push(Triangle { area: area(index - 1, index, index + 1), index, version: 0 });
+    }
+
+    while let Some(triangle) = heap.pop() {
+        if triangle.version != versions[triangle.index] {
+            continue;
+        }
+
+        if triangle.area >= min_area {
+            break;
+        }
+
+        let index = triangle.index;
+        let p = previous[index];
+        let n = next[index];
+        removed[index] = true;
+        next[p] = n;
+        previous[n] = p;
+
+        for neighbor in [p, n] {
+            if neighbor == 0 || neighbor == count - 1 {
+                continue;
+            }
+
+            versions[neighbor] += 1;
+            heap.push(Triangle { area: area(previous[neighbor], neighbor, next[neighbor]), index: neighbor, version: versions[neighbor] });
+        }
+    }
+
+    positions.iter().zip(removed).filter(|(_, removed)| !*removed).map(|(position, _)| *position).collect()
+}
+
+fn segment_distance(point : Vec2, start : Vec2, end : Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared < f32::EPSILON {
+        return point.distance(start);
+    }
+
+    let t = ((point - start).dot(segment) / length_squared).clamp(0_f32, 1_f32);
+    point.distance(start + segment * t)
+}
+
+/// Simplifies every run of `LineTo`, curves are kept untouched and split the runs.
+/// The tolerance is expressed in the same unit as the instructions.
+pub fn simplify_instructions(instructions : &[Instruction], method : SimplificationMethod, tolerance : f32, statistics : &mut SimplificationStatistics) -> Vec<Instruction> {
+    let mut simplified = Vec::with_capacity(instructions.len());
+    let mut polyline : Vec<Vec2> = Vec::new();
+
+    let mut flush = |polyline : &mut Vec<Vec2>, simplified : &mut Vec<Instruction>| {
+        if polyline.len() > 1 {
+            let points = match method {
+                SimplificationMethod::RamerDouglasPeucker => ramer_douglas_peucker(polyline, tolerance),
+                SimplificationMethod::VisvalingamWhyatt => visvalingam_whyatt(polyline, tolerance),
+            };
+
+            statistics.points_before += polyline.len() - 1;
+            statistics.points_after += points.len() - 1;
+            simplified.extend(points.into_iter().skip(1).map(Instruction::LineTo));
+        }
+
+        polyline.clear();
+    };
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::LineTo(pos) => {
+                if polyline.is_empty() {
+                    polyline.push(Vec2::ZERO);
+                }
+
+                polyline.push(*pos);
+            },
+            _ => {
+                flush(&mut polyline, &mut simplified);
+                simplified.push(*instruction);
+                polyline.push(instruction.end_point());
+            },
+        }
+    }
+
+    flush(&mut polyline, &mut simplified);
+
+    simplified
+}
+
+/// Simplified copy of the drawing, curves being kept as they are and only the line runs between them simplified.
+pub fn simplify_drawing(drawing : &Drawing, method : SimplificationMethod, tolerance : f32, statistics : &mut SimplificationStatistics) -> Drawing {
+    let mut simplified = Drawing::new();
+    for stroke in drawing {
+        simplified.add_instructions(&simplify_instructions(stroke.instructions(), method, tolerance, statistics), &stroke.attributes);
+    }
+
+    simplified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS : [SimplificationMethod; 2] = [SimplificationMethod::RamerDouglasPeucker, SimplificationMethod::VisvalingamWhyatt];
+
+    fn simplify(positions : &[Vec2], method : SimplificationMethod, tolerance : f32) -> Vec<Vec2> {
+        match method {
+            SimplificationMethod::RamerDouglasPeucker => ramer_douglas_peucker(positions, tolerance),
+            SimplificationMethod::VisvalingamWhyatt => visvalingam_whyatt(positions, tolerance),
+        }
+    }
+
+    fn zigzag() -> Vec<Vec2> {
+        (0..20).map(|index| Vec2::new(index as f32 * 3_f32, ((index * 7) % 5) as f32 * 0.4_f32)).collect()
+    }
+
+    #[test]
+    fn simplification_keeps_the_endpoints() {
+        let positions = zigzag();
+        for method in METHODS {
+            for tolerance in [0.01_f32, 0.5_f32, 100_f32] {
+                let simplified = simplify(&positions, method, tolerance);
+                assert!(simplified.len() >= 2);
+                assert_eq!(simplified.first(), positions.first(), "{:?}", method);
+                assert_eq!(simplified.last(), positions.last(), "{:?}", method);
+            }
+
+            assert_eq!(simplify(&positions, method, 100_f32).len(), 2, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn simplification_drops_collinear_points() {
+        let positions = vec![Vec2::new(0_f32, 0_f32), Vec2::new(1_f32, 1_f32), Vec2::new(2_f32, 2_f32), Vec2::new(5_f32, 5_f32), Vec2::new(5_f32, 0_f32), Vec2::new(5_f32, -3_f32), Vec2::new(5_f32, -8_f32)];
+        let expected = vec![Vec2::new(0_f32, 0_f32), Vec2::new(5_f32, 5_f32), Vec2::new(5_f32, -8_f32)];
+        for method in METHODS {
+            assert_eq!(simplify(&positions, method, 0.01_f32), expected, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn statistics_count_the_segments_of_line_runs() {
+        let mut instructions = vec![Instruction::MoveTo(Vec2::ZERO)];
+        instructions.extend((1..6).map(|index| Instruction::LineTo(Vec2::new(index as f32, 0_f32))));
+        instructions.push(Instruction::QuadTo(Vec2::new(6_f32, 2_f32), Vec2::new(7_f32, 0_f32)));
+        instructions.extend((8..11).map(|index| Instruction::LineTo(Vec2::new(index as f32, 0_f32))));
+
+        for method in METHODS {
+            let mut statistics = SimplificationStatistics::default();
+            let simplified = simplify_instructions(&instructions, method, 0.01_f32, &mut statistics);
+            assert_eq!(statistics.points_before, 8);
+            assert_eq!(statistics.points_after, 2);
+            assert_eq!(statistics.points_removed(), 6);
+            assert_eq!(simplified.len(), 4);
+            assert!(matches!(simplified[2], Instruction::QuadTo(..)), "{:?}", method);
+            assert_eq!(simplified[3].end_point(), Vec2::new(10_f32, 0_f32));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use glam::*;
+
+use crate::drawing::*;
+use crate::simplify::*;
+use crate::utils::*;
+
+#[derive(Copy, Clone)]
+pub struct SketchParameters {
+    /// None keeps the mouse path as drawn.
+    pub smoothing_mode : Option<SmoothingMode>,
+    pub smooth_number_of_points : usize,
+    pub smooth_sharpness : f32,
+    /// Applied to the raw mouse path before smoothing, tolerance in millimetres.
+    pub simplification : Option<SimplificationParameters>,
+}
+
+impl Default for SketchParameters {
+    fn default() -> SketchParameters {
+        SketchParameters {
+            smoothing_mode: Some(SmoothingMode::CatmullRom),
+            smooth_number_of_points: 4,
+            smooth_sharpness: 0.5_f32,
+            simplification: Some(SimplificationParameters { method: SimplificationMethod::RamerDouglasPeucker, tolerance: 0.2_f32 }),
+        }
+    }
+}
+
+/// A stroke being drawn with the mouse, kept apart from the generated content so it survives regeneration once finished.
+#[derive(Default)]
+pub struct Sketch {
+    current : Vec<Vec2>,
+}
+
+impl Sketch {
+    /// The stroke being drawn, as raw mouse positions.
+    pub fn current(&self) -> &[Vec2] {
+        &self.current
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        !self.current.is_empty()
+    }
+
+    /// Positions closer than `min_distance` to the previous one are skipped, mouse jitter being noise.
+    pub fn add_point(&mut self, position: Vec2, min_distance: f32) {
+        match self.current.last() {
+            Some(last) if last.distance(position) < min_distance => (),
+            _ => self.current.push(position),
+        }
+    }
+
+    /// Turns the stroke being drawn into instructions, `scale` converting millimetres to drawing units.
+    pub fn finish_stroke(&mut self, parameters: &SketchParameters, scale: f32) -> Option<Stroke> {
+        let mut positions = std::mem::take(&mut self.current);
+        if positions.len() < 2 {
+            return None;
+        }
+
+        if let Some(simplification) = parameters.simplification {
+            positions = match simplification.method {
+                SimplificationMethod::RamerDouglasPeucker => ramer_douglas_peucker(&positions, simplification.tolerance * scale),
+                SimplificationMethod::VisvalingamWhyatt => visvalingam_whyatt(&positions, simplification.tolerance * scale),
+            };
+        }
+
+        let mut instructions = Vec::new();
+        match parameters.smoothing_mode {
+            Some(mode) if positions.len() > 2 => smooth_into_instructions(positions, mode, parameters.smooth_number_of_points, parameters.smooth_sharpness, &mut instructions),
+            _ => {
+                instructions.push(Instruction::MoveTo(positions[0]));
+                instructions.extend(positions.iter().skip(1).map(|position| Instruction::LineTo(*position)));
+            },
+        }
+
+        let mut drawing = Drawing::new();
+        drawing.add_instructions(&instructions, &StrokeAttributes::new("sketch"));
+        drawing.strokes.pop()
+    }
+}
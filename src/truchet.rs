@@ -0,0 +1,204 @@
+use glam::*;
+
+use crate::grid::*;
+use crate::utils::*;
+
+/// Start, first control, second control and end point of a cubic curve.
+pub type Cubic = (Vec2, Vec2, Vec2, Vec2);
+
+/// Sides of a tile joined by its motif, as pairs of edge ids, `rotation` turning the motif by that many sides.
+///
+/// Every other side is joined to the next one, a side being left alone when the tile has an odd number of them.
+/// Along finer tiles a side holds several edges, only the ones meeting at the corner of the joined sides are used.
+pub fn truchet_connections(grid: &Grid, tile: usize, rotation: usize) -> Vec<(usize, usize)> {
+    let sides = grid.tile_sides(tile);
+    let count = sides.len();
+    (0..count / 2).map(|pair| {
+        let (from, to) = (&sides[(rotation + 2 * pair) % count], &sides[(rotation + 2 * pair + 1) % count]);
+        (from[from.len() - 1], to[0])
+    }).collect()
+}
+
+/// Arc inside a tile from the middle of one edge to the middle of the other, centered on their shared corner.
+///
+/// Both ends leave their edge at a right angle so arcs of neighboring tiles meet smoothly.
+pub fn truchet_arc(grid: &Grid, tile: usize, from: usize, to: usize) -> Cubic {
+    let (from, to) = (grid.edges[from], grid.edges[to]);
+    let middle = |edge: GridEdge| (grid.vertices[edge.from] + grid.vertices[edge.to]) / 2_f32;
+    let (start, end) = (middle(from), middle(to));
+
+    let Some(corner) = [from.from, from.to].into_iter().find(|vertex| *vertex == to.from || *vertex == to.to) else {
+        return (start, start.lerp(end, 1_f32 / 3_f32), start.lerp(end, 2_f32 / 3_f32), end);
+    };
+
+    let corner = grid.vertices[corner];
+    let center = grid.tiles[tile].position;
+    // Handle length of a cubic curve following a circular arc of that angle, relative to the radius.
+    let handle = 4_f32 / 3_f32 * ((start - corner).angle_between(end - corner).abs() / 4_f32).tan();
+    let inward = |point: Vec2| {
+        let normal = (point - corner).perp();
+        match normal.dot(center - point) < 0_f32 {
+            true => -normal,
+            false => normal,
+        }
+    };
+
+    (start, start + inward(start) * handle, end + inward(end) * handle, end)
+}
+
+/// Arcs of every tile joined through the edges they share into continuous curves, ordered so the pen travels little between them.
+///
+/// `rotations` holds the motif rotation of each tile, tiles without one being left empty.
+pub fn truchet_curves(grid: &Grid, rotations: &[Option<usize>]) -> Vec<Vec<Instruction>> {
+    // Arcs as tile, edge and edge, with the arcs reaching each edge.
+    let mut arcs : Vec<(usize, usize, usize)> = Vec::new();
+    let mut reaching : Vec<Vec<usize>> = vec![Vec::new(); grid.edges.len()];
+    for tile in &grid.tiles {
+        let Some(rotation) = rotations.get(tile.index).copied().flatten() else { continue };
+        for (from, to) in truchet_connections(grid, tile.index, rotation) {
+            reaching[from].push(arcs.len());
+            reaching[to].push(arcs.len());
+            arcs.push((tile.index, from, to));
+        }
+    }
+
+    // Open curves first, starting from the edges reached by a single arc, then the closed loops.
+    let open_starts = (0..grid.edges.len()).filter(|edge| reaching[*edge].len() == 1).map(|edge| (edge, reaching[edge][0]));
+    let loop_starts = arcs.iter().enumerate().map(|(arc, (_, from, _))| (*from, arc));
+    let mut used = vec![false; arcs.len()];
+    let mut curves : Vec<Vec<Cubic>> = Vec::new();
+    for (mut edge, first_arc) in open_starts.chain(loop_starts).collect::<Vec<_>>() {
+        if used[first_arc] {
+            continue;
+        }
+
+        let mut curve = Vec::new();
+        let mut next_arc = Some(first_arc);
+        while let Some(arc) = next_arc {
+            used[arc] = true;
+            let (tile, from, to) = arcs[arc];
+            let (from, to) = match from == edge {
+                true => (from, to),
+                false => (to, from),
+            };
+
+            curve.push(truchet_arc(grid, tile, from, to));
+            edge = to;
+            next_arc = reaching[edge].iter().copied().find(|other| !used[*other]);
+        }
+
+        curves.push(curve);
+    }
+
+    order_curves(curves).into_iter().map(|curve| {
+        let mut instructions = vec![Instruction::MoveTo(curve[0].0)];
+        instructions.extend(curve.iter().map(|(_, control1, control2, end)| Instruction::CubicTo(*control1, *control2, *end)));
+        instructions
+    }).collect()
+}
+
+// Nearest curve next, open curves being turned around when their end is closer than their start.
+fn order_curves(mut remaining: Vec<Vec<Cubic>>) -> Vec<Vec<Cubic>> {
+    let mut ordered : Vec<Vec<Cubic>> = Vec::with_capacity(remaining.len());
+    let mut position = Vec2::ZERO;
+    while !remaining.is_empty() {
+        let distance = |curve: &Vec<Cubic>| position.distance_squared(curve[0].0).min(position.distance_squared(curve[curve.len() - 1].3));
+        let nearest = (0..remaining.len()).min_by(|a, b| distance(&remaining[*a]).total_cmp(&distance(&remaining[*b]))).unwrap();
+        let mut curve = remaining.swap_remove(nearest);
+        if position.distance_squared(curve[curve.len() - 1].3) < position.distance_squared(curve[0].0) {
+            curve.reverse();
+            for cubic in &mut curve {
+                *cubic = (cubic.3, cubic.2, cubic.1, cubic.0);
+            }
+        }
+
+        position = curve[curve.len() - 1].3;
+        ordered.push(curve);
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grids() -> Vec<Grid> {
+        let mut subdivided = Grid::hex_grid(4, 3, 10_f32, Vec2::ZERO);
+        subdivided.subdivide(2, |tile| tile.position.x < 30_f32);
+        vec![Grid::hex_grid(5, 4, 10_f32, Vec2::ZERO), subdivided]
+    }
+
+    #[test]
+    fn curves_stitch_every_arc_once() {
+        let near = |a: Vec2, b: Vec2| a.distance(b) < 1e-3_f32;
+        for grid in grids() {
+            // Mixed rotations with a few empty tiles, so curves end on empty tiles as well as on the border,
+            // then the same rotation everywhere, which closes small loops around corners.
+            let mixed = (0..grid.tiles.len()).map(|index| match index % 7 {
+                3 => None,
+                _ => Some(index * 5 % 6),
+            }).collect();
+
+            for (rotations, closes_loops) in [(mixed, false), (vec![Some(0); grid.tiles.len()], true)] {
+                let mut arcs : Vec<Cubic> = Vec::new();
+                for tile in &grid.tiles {
+                    if let Some(rotation) = rotations[tile.index] {
+                        arcs.extend(truchet_connections(&grid, tile.index, rotation).into_iter().map(|(from, to)| truchet_arc(&grid, tile.index, from, to)));
+                    }
+                }
+
+                // Ends of arcs meeting no other arc are where open curves start and stop.
+                let ends : Vec<Vec2> = arcs.iter().flat_map(|arc| [arc.0, arc.3]).collect();
+                let is_free = |point: Vec2| ends.iter().filter(|end| near(**end, point)).count() == 1;
+
+                let mut used = vec![false; arcs.len()];
+                let mut loop_count = 0;
+                for curve in truchet_curves(&grid, &rotations) {
+                    let Instruction::MoveTo(start) = curve[0] else { panic!("curves start with a move") };
+                    let mut position = start;
+                    for instruction in &curve[1..] {
+                        let Instruction::CubicTo(control1, control2, end) = *instruction else { panic!("curves are made of cubics") };
+                        // Each cubic starts where the previous one ended, and is one of the arcs in either direction.
+                        let arc = (0..arcs.len()).find(|arc| !used[*arc] && {
+                            let (a, b, c, d) = arcs[*arc];
+                            (near(a, position) && near(b, control1) && near(c, control2) && near(d, end)) || (near(d, position) && near(c, control1) && near(b, control2) && near(a, end))
+                        });
+
+                        used[arc.expect("every cubic is an arc not drawn yet")] = true;
+                        position = end;
+                    }
+
+                    match is_free(start) {
+                        true => assert!(is_free(position), "open curve from {} stops at {} among other arcs", start, position),
+                        false => {
+                            assert!(near(start, position), "closed curve from {} ends at {}", start, position);
+                            loop_count += 1;
+                        },
+                    }
+                }
+
+                assert!(used.iter().all(|used| *used), "some arcs are not drawn");
+                if closes_loops {
+                    assert!(loop_count > 0, "the same rotation everywhere closes loops");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn connections_turn_at_corners() {
+        let grid = grids().remove(1);
+        for tile in &grid.tiles {
+            for rotation in 0..6 {
+                for (from, to) in truchet_connections(&grid, tile.index, rotation) {
+                    let (from, to) = (grid.edges[from], grid.edges[to]);
+                    let corner = [from.from, from.to].into_iter().find(|vertex| *vertex == to.from || *vertex == to.to).expect("joined edges share a corner");
+                    let other = |edge: GridEdge| grid.vertices[match edge.from == corner { true => edge.to, false => edge.from }] - grid.vertices[corner];
+                    let (a, b) = (other(from).normalize(), other(to).normalize());
+                    assert!(a.dot(b) > -0.99_f32, "tile {} joins two halves of a side", tile.index);
+                }
+            }
+        }
+    }
+}
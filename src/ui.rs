@@ -0,0 +1,282 @@
+use ggez::{*, graphics::{Color, DrawMode, MeshBuilder, Rect}};
+use glam::*;
+
+use crate::drawing::*;
+use crate::generator::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ControlValue {
+    Number { value : f32, min : f32, max : f32, integer : bool },
+    Toggle(bool),
+}
+
+#[derive(Clone, Debug)]
+pub struct Control {
+    pub name : String,
+    pub value : ControlValue,
+}
+
+impl Control {
+    pub fn slider(name: &str, value: f32, min: f32, max: f32, integer: bool) -> Control {
+        Control { name: name.to_owned(), value: ControlValue::Number { value, min, max, integer } }
+    }
+
+    pub fn toggle(name: &str, value: bool) -> Control {
+        Control { name: name.to_owned(), value: ControlValue::Toggle(value) }
+    }
+
+    pub fn number(&self) -> f32 {
+        match self.value {
+            ControlValue::Number { value, .. } => value,
+            ControlValue::Toggle(value) => match value { true => 1_f32, false => 0_f32 },
+        }
+    }
+    fn label(&self) -> String {
+        match self.value {
+            ControlValue::Number { value, integer: true, .. } => format!("{} : {}", self.name, value.round() as i64),
+            ControlValue::Number { value, .. } => format!("{} : {:.3}", self.name, value),
+            ControlValue::Toggle(_) => self.name.clone(),
+        }
+    }
+}
+
+impl From<Parameter> for Control {
+    fn from(parameter: Parameter) -> Control {
+        Control::slider(parameter.name, parameter.value, parameter.min, parameter.max, parameter.integer)
+    }
+}
+
+// Column of sliders and toggles drawn over the preview, one row per control.
+pub struct Panel {
+    pub visible : bool,
+    position : Vec2,
+    width : f32,
+    dragging : Option<usize>,
+}
+
+impl Panel {
+    const ROW_HEIGHT : f32 = 26_f32;
+    const PADDING : f32 = 6_f32;
+    const TEXT_SIZE : f32 = 12_f32;
+
+    pub fn new(position: Vec2, width: f32) -> Panel {
+        Panel { visible: false, position, width, dragging: None }
+    }
+
+    fn bounds(&self, count: usize) -> Bounds {
+        let size = Vec2::new(self.width, count as f32 * Panel::ROW_HEIGHT + Panel::PADDING * 2_f32);
+        Bounds { min: self.position, max: self.position + size }
+    }
+
+    // Area of the track or box of a row, under its label.
+    fn widget(&self, row: usize) -> Bounds {
+        let top = self.position.y + Panel::PADDING + row as f32 * Panel::ROW_HEIGHT + Panel::TEXT_SIZE + 2_f32;
+        let min = Vec2::new(self.position.x + Panel::PADDING, top);
+        Bounds { min, max: min + Vec2::new(self.width - Panel::PADDING * 2_f32, Panel::ROW_HEIGHT - Panel::TEXT_SIZE - 6_f32) }
+    }
+
+    fn row_at(&self, count: usize, point: Vec2) -> Option<usize> {
+        if !self.contains(count, point) {
+            return None;
+        }
+
+        let row = ((point.y - self.position.y - Panel::PADDING) / Panel::ROW_HEIGHT).floor();
+        match row >= 0_f32 && (row as usize) < count {
+            true => Some(row as usize),
+            false => None,
+        }
+    }
+
+    pub fn contains(&self, count: usize, point: Vec2) -> bool {
+        self.visible && self.bounds(count).contains(point)
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragging.is_some()
+    }
+
+    // Feeds the mouse state, returns the index of the control whose value changed.
+    pub fn update(&mut self, controls: &mut [Control], mouse: Vec2, is_down: bool, was_down: bool) -> Option<usize> {
+        if !self.visible || !is_down {
+            self.dragging = None;
+            return None;
+        }
+
+        if !was_down {
+            let row = self.row_at(controls.len(), mouse)?;
+            if let ControlValue::Toggle(value) = &mut controls[row].value {
+                *value = !*value;
+                return Some(row);
+            }
+
+            self.dragging = Some(row);
+        }
+
+        let row = self.dragging?;
+        let widget = self.widget(row);
+        if let ControlValue::Number { value, min, max, integer } = &mut controls[row].value {
+            let ratio = ((mouse.x - widget.min.x) / widget.size().x).clamp(0_f32, 1_f32);
+            let mut new_value = *min + (*max - *min) * ratio;
+            if *integer {
+                new_value = new_value.round();
+            }
+
+            if (new_value - *value).abs() > f32::EPSILON {
+                *value = new_value;
+                return Some(row);
+            }
+        }
+
+        None
+    }
+}
+impl Panel {
+    pub fn draw(&self, ctx: &mut Context, controls: &[Control]) -> GameResult {
+        if !self.visible || controls.is_empty() {
+            return Ok(());
+        }
+
+        let mb = &mut MeshBuilder::new();
+        let bounds = rect(&self.bounds(controls.len()));
+        mb.rectangle(DrawMode::fill(), bounds, Color::new(1_f32, 1_f32, 1_f32, 0.85_f32))?;
+        mb.rectangle(DrawMode::stroke(1_f32), bounds, Color::BLACK)?;
+
+        for (row, control) in controls.iter().enumerate() {
+            let widget = rect(&self.widget(row));
+            match control.value {
+                ControlValue::Number { value, min, max, .. } => {
+                    let middle = widget.y + widget.h / 2_f32;
+                    mb.line(&[mint::Point2 { x: widget.x, y: middle }, mint::Point2 { x: widget.x + widget.w, y: middle }], 1_f32, Color::BLACK)?;
+
+                    let ratio = match max > min { true => ((value - min) / (max - min)).clamp(0_f32, 1_f32), false => 0_f32 };
+                    let handle = Rect::new(widget.x + widget.w * ratio - 3_f32, widget.y, 6_f32, widget.h);
+                    mb.rectangle(DrawMode::fill(), handle, Color::BLACK)?;
+                },
+                ControlValue::Toggle(value) => {
+                    let check = Rect::new(widget.x, widget.y, widget.h, widget.h);
+                    mb.rectangle(DrawMode::stroke(1_f32), check, Color::BLACK)?;
+                    if value {
+                        mb.rectangle(DrawMode::fill(), Rect::new(check.x + 2_f32, check.y + 2_f32, check.w - 4_f32, check.h - 4_f32), Color::BLACK)?;
+                    }
+                },
+            }
+
+            let text = graphics::Text::new(graphics::TextFragment::new(control.label()).scale(Panel::TEXT_SIZE));
+            let top = self.position.y + Panel::PADDING + row as f32 * Panel::ROW_HEIGHT;
+            graphics::queue_text(ctx, &text, mint::Point2 { x: self.position.x + Panel::PADDING, y: top }, Some(Color::BLACK));
+        }
+
+        let mesh = mb.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())?;
+        graphics::draw_queued_text(ctx, graphics::DrawParam::new(), None, graphics::FilterMode::Linear)
+    }
+}
+
+// Pan and zoom of the preview, drawing units being mapped to `position * zoom + offset` on screen.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct View {
+    pub offset : Vec2,
+    pub zoom : f32,
+}
+
+impl View {
+    pub fn new() -> View {
+        View { offset: Vec2::ZERO, zoom: 1_f32 }
+    }
+
+    pub fn screen_position(&self, position: Vec2) -> Vec2 {
+        position * self.zoom + self.offset
+    }
+
+    pub fn world_position(&self, position: Vec2) -> Vec2 {
+        (position - self.offset) / self.zoom
+    }
+
+    // Zooms keeping the point under `anchor` in place.
+    pub fn zoom_at(&mut self, anchor: Vec2, factor: f32) {
+        let world = self.world_position(anchor);
+        self.zoom = (self.zoom * factor).clamp(0.25_f32, 40_f32);
+        self.offset = anchor - world * self.zoom;
+    }
+    pub fn draw_param(&self) -> graphics::DrawParam {
+        graphics::DrawParam::new()
+            .dest(mint::Point2 { x: self.offset.x, y: self.offset.y })
+            .scale(mint::Vector2 { x: self.zoom, y: self.zoom })
+    }
+}
+
+impl Default for View {
+    fn default() -> View {
+        View::new()
+    }
+}
+fn rect(bounds: &Bounds) -> Rect {
+    let size = bounds.size();
+    Rect::new(bounds.min.x, bounds.min.y, size.x, size.y)
+}
+fn segment(mb: &mut MeshBuilder, from: Vec2, to: Vec2, line_width: f32, color: Color) -> GameResult {
+    mb.line(&[mint::Point2 { x: from.x, y: from.y }, mint::Point2 { x: to.x, y: to.y }], line_width, color)?;
+    Ok(())
+}
+fn rectangle(mb: &mut MeshBuilder, bounds: &Bounds, line_width: f32, color: Color) -> GameResult {
+    mb.rectangle(DrawMode::stroke(line_width), rect(bounds), color)?;
+    Ok(())
+}
+
+// Outline of the sheet and of the area the plot should stay in.
+pub fn fill_page_frame(mb: &mut MeshBuilder, page: &Bounds, safe_area: &Bounds, line_width: f32) -> GameResult {
+    rectangle(mb, page, line_width, Color::BLACK)?;
+    rectangle(mb, safe_area, line_width, Color::new(0.2_f32, 0.4_f32, 1_f32, 1_f32))
+}
+
+// Light lines every centimetre over the page, every millimetre once they are far enough apart on screen.
+pub fn fill_millimetre_grid(mb: &mut MeshBuilder, page: &Bounds, scale: f32, view: &View, line_width: f32) -> GameResult {
+    let step = match scale * view.zoom >= 6_f32 { true => 1, false => 10 };
+    let size = page.size() / scale;
+    for millimetre in (0..=size.x as usize).step_by(step) {
+        let color = match millimetre % 10 { 0 => Color::new(0_f32, 0_f32, 0_f32, 0.25_f32), _ => Color::new(0_f32, 0_f32, 0_f32, 0.08_f32) };
+        let x = page.min.x + millimetre as f32 * scale;
+        segment(mb, Vec2::new(x, page.min.y), Vec2::new(x, page.max.y), line_width, color)?;
+    }
+
+    for millimetre in (0..=size.y as usize).step_by(step) {
+        let color = match millimetre % 10 { 0 => Color::new(0_f32, 0_f32, 0_f32, 0.25_f32), _ => Color::new(0_f32, 0_f32, 0_f32, 0.08_f32) };
+        let y = page.min.y + millimetre as f32 * scale;
+        segment(mb, Vec2::new(page.min.x, y), Vec2::new(page.max.x, y), line_width, color)?;
+    }
+
+    Ok(())
+}
+
+// Centimetre labels along the top and left edges of the page, in screen space.
+pub fn queue_ruler_labels(ctx: &mut Context, page: &Bounds, scale: f32, view: &View) {
+    let size = page.size() / scale;
+    let label = |ctx: &mut Context, text: String, position: Vec2| {
+        let text = graphics::Text::new(graphics::TextFragment::new(text).scale(10_f32));
+        graphics::queue_text(ctx, &text, mint::Point2 { x: position.x, y: position.y }, Some(Color::new(0_f32, 0_f32, 0_f32, 0.6_f32)));
+    };
+
+    for millimetre in (10..=size.x as usize).step_by(10) {
+        let position = view.screen_position(Vec2::new(page.min.x + millimetre as f32 * scale, page.min.y));
+        label(ctx, format!("{}", millimetre), position + Vec2::new(2_f32, 2_f32));
+    }
+
+    for millimetre in (10..=size.y as usize).step_by(10) {
+        let position = view.screen_position(Vec2::new(page.min.x, page.min.y + millimetre as f32 * scale));
+        label(ctx, format!("{}", millimetre), position + Vec2::new(2_f32, -12_f32));
+    }
+}
+pub fn fill_dashed_segments(mb: &mut MeshBuilder, segments: &[(Vec2, Vec2)], dash: f32, line_width: f32, color: Color) -> GameResult {
+    for (from, to) in segments {
+        let length = from.distance(*to);
+        let direction = (*to - *from) / length;
+        let mut start = 0_f32;
+        while start < length {
+            let end = (start + dash).min(length);
+            segment(mb, *from + direction * start, *from + direction * end, line_width, color)?;
+            start += dash * 2_f32;
+        }
+    }
+
+    Ok(())
+}
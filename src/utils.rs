@@ -1,6 +1,8 @@
 use svg::node::element::path::{Parameters, Data};
 use glam::*;
 
+use crate::drawing::*;
+
 pub trait ToParameters {
     fn from(&self) -> Parameters;
 }
@@ -26,62 +28,225 @@ impl OrderedPair{
         let p2x = (p2.x * 1000_f32) as i32;
         let p2y = (p2.y * 1000_f32) as i32;
 
-        if p1x > p2x {
+        if p1x > p2x || (p1x == p2x && p1y > p2y) {
             return OrderedPair {p1x:p2x, p1y:p2y, p2x:p1x, p2y: p1y};
         }
-        else if p1x == p2x {
-            if p1y > p2y {
-                return OrderedPair {p1x:p2x, p1y:p2y, p2x:p1x, p2y: p1y};
-            }
-        }
 
-        return OrderedPair {p1x:p1x, p1y:p1y, p2x:p2x, p2y: p2y};
+        OrderedPair {p1x, p1y, p2x, p2y}
     }
 }
 
+#[derive(Copy, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Instruction {
     MoveTo(Vec2),
     LineTo(Vec2),
+    /// Control point, end point.
+    QuadTo(Vec2, Vec2),
+    /// First control point, second control point, end point.
+    CubicTo(Vec2, Vec2, Vec2),
 }
 
 impl Instruction {
-    fn execute_instruction(&self, data: Data) -> Data {
+    pub fn execute_instruction(&self, data: Data, scale: f32) -> Data {
         match self {
             Instruction::MoveTo(position) => {
-                data.move_to(ToParameters::from(position))
+                data.move_to(ToParameters::from(&(*position / scale)))
             },
 
             Instruction::LineTo(position) => {
-                data.line_to(ToParameters::from(position))
+                data.line_to(ToParameters::from(&(*position / scale)))
+            },
+
+            Instruction::QuadTo(control, position) => {
+                let control = *control / scale;
+                let position = *position / scale;
+                data.quadratic_curve_to((control.x, control.y, position.x, position.y))
+            },
+
+            Instruction::CubicTo(control1, control2, position) => {
+                let control1 = *control1 / scale;
+                let control2 = *control2 / scale;
+                let position = *position / scale;
+                data.cubic_curve_to((control1.x, control1.y, control2.x, control2.y, position.x, position.y))
             },
         }
     }
+
+    pub fn end_point(&self) -> Vec2 {
+        match self {
+            Instruction::MoveTo(position) | Instruction::LineTo(position) | Instruction::QuadTo(_, position) | Instruction::CubicTo(_, _, position) => *position,
+        }
+    }
+
+    pub fn map<F>(&self, f: F) -> Instruction where F: Fn(Vec2) -> Vec2 {
+        match self {
+            Instruction::MoveTo(position) => Instruction::MoveTo(f(*position)),
+            Instruction::LineTo(position) => Instruction::LineTo(f(*position)),
+            Instruction::QuadTo(control, position) => Instruction::QuadTo(f(*control), f(*position)),
+            Instruction::CubicTo(control1, control2, position) => Instruction::CubicTo(f(*control1), f(*control2), f(*position)),
+        }
+    }
+}
+
+/// Replaces curve instructions by `nb_points` line segments each, for consumers that can only draw polylines.
+pub fn flatten_instructions(instructions : &[Instruction], nb_points: usize) -> Vec<Instruction> {
+    let nb_points = nb_points.max(1);
+    let mut flattened = Vec::with_capacity(instructions.len());
+    let mut current_position = Vec2::ZERO;
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::MoveTo(pos) => flattened.push(Instruction::MoveTo(*pos)),
+            Instruction::LineTo(pos) => flattened.push(Instruction::LineTo(*pos)),
+            Instruction::QuadTo(control, pos) => {
+                for pt in 1..(nb_points + 1) {
+                    let s = pt as f32 / nb_points as f32;
+                    let l1 = current_position.lerp(*control, s);
+                    let l2 = control.lerp(*pos, s);
+                    flattened.push(Instruction::LineTo(l1.lerp(l2, s)));
+                }
+            },
+            Instruction::CubicTo(control1, control2, pos) => {
+                for pt in 1..(nb_points + 1) {
+                    let s = pt as f32 / nb_points as f32;
+                    flattened.push(Instruction::LineTo(cubic_point(current_position, *control1, *control2, *pos, s)));
+                }
+            },
+        }
+
+        current_position = instruction.end_point();
+    }
+
+    flattened
+}
+
+/// Even-odd test, the polygon being implicitly closed.
+pub fn polygon_contains(polygon : &[Vec2], point : Vec2) -> bool {
+    let mut inside = false;
+    for index in 0..polygon.len() {
+        let a = polygon[index];
+        let b = polygon[(index + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+pub fn cubic_point(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, s: f32) -> Vec2 {
+    let r = 1_f32 - s;
+    p0 * (r * r * r) + p1 * (3_f32 * r * r * s) + p2 * (3_f32 * r * s * s) + p3 * (s * s * s)
 }
 
-pub fn print_circle_to_instructions(position : Vec2, radius : f32, nb_vertice: i32, instructions : &mut Vec<Instruction>) {
+#[allow(dead_code)]
+pub fn print_circle_to_drawing(position : Vec2, radius : f32, nb_vertice: i32, attributes : &StrokeAttributes, drawing : &mut Drawing) {
     let co = (std::f32::consts::TAU / nb_vertice as f32).cos();
     let si = (std::f32::consts::TAU / nb_vertice as f32).sin();
     let mut x = 0_f32;
     let mut y = 1_f32;
-    instructions.push(Instruction::MoveTo(position + Vec2::new(x, y) * radius));
-    for _ in 0..nb_vertice {
+    let mut stroke = Stroke::new(position + Vec2::new(x, y) * radius, StrokeAttributes { closed: true, ..attributes.clone() });
+    for _ in 1..nb_vertice {
         let nx = x * co - y * si;
         let ny = x * si + y * co;
-        
+
         x = nx;
         y = ny;
-        instructions.push(Instruction::LineTo(position + Vec2::new(x, y) * radius));
+        stroke.line_to(position + Vec2::new(x, y) * radius);
+    }
+
+    drawing.add(stroke);
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SmoothingMode {
+    /// Corner cutting with a quadratic arc sampled in `nb_points` segments.
+    Quadratic,
+    /// Chaikin corner cutting, iterated until corners are cut in about `nb_points` segments.
+    Chaikin,
+    /// Centripetal Catmull-Rom spline through the points, `nb_points` segments per span.
+    CatmullRom,
+    /// Same arcs as `Quadratic`, exported as `Q` commands.
+    QuadraticCurve,
+    /// Centripetal Catmull-Rom spline converted to `C` commands.
+    CubicBezier,
+}
+
+impl SmoothingMode {
+    pub fn next(self) -> SmoothingMode {
+        match self {
+            SmoothingMode::Quadratic => SmoothingMode::Chaikin,
+            SmoothingMode::Chaikin => SmoothingMode::CatmullRom,
+            SmoothingMode::CatmullRom => SmoothingMode::QuadraticCurve,
+            SmoothingMode::QuadraticCurve => SmoothingMode::CubicBezier,
+            SmoothingMode::CubicBezier => SmoothingMode::Quadratic,
+        }
+    }
+}
+
+/// How polylines are rounded before being drawn.
+#[derive(Copy, Clone)]
+pub struct SmoothingParameters {
+    pub smooth_number_of_points : usize,
+    pub smooth_sharpness : f32,
+    pub smoothing_mode : SmoothingMode,
+}
+
+pub fn smooth_into_instructions(positions : Vec<Vec2>, mode: SmoothingMode, nb_points: usize, sharpness: f32, instructions : &mut Vec<Instruction>) {
+    if positions.len() < 2 {
+        return;
+    }
+
+    match mode {
+        SmoothingMode::Quadratic | SmoothingMode::Chaikin | SmoothingMode::CatmullRom => {
+            let smoothed = match mode {
+                SmoothingMode::Chaikin => chaikin(positions, chaikin_iterations(nb_points), sharpness),
+                SmoothingMode::CatmullRom => catmull_rom(positions, nb_points),
+                _ => smooth(positions, nb_points, sharpness),
+            };
+
+            instructions.push(Instruction::MoveTo(smoothed[0]));
+            for position in smoothed.iter().skip(1) {
+                instructions.push(Instruction::LineTo(*position));
+            }
+        },
+
+        SmoothingMode::QuadraticCurve => {
+            instructions.push(Instruction::MoveTo(positions[0]));
+            for index in 1..(positions.len() - 1) {
+                let p1 = positions[index - 1];
+                let p2 = positions[index];
+                let p3 = positions[index + 1];
+
+                let p1 = (p1 + p2) / 2_f32;
+                let p3 = (p3 + p2) / 2_f32;
+                let p1 = p1 + (p2 - p1) * sharpness;
+                let p3 = p3 + (p2 - p3) * sharpness;
+
+                instructions.push(Instruction::LineTo(p1));
+                instructions.push(Instruction::QuadTo(p2, p3));
+            }
+
+            instructions.push(Instruction::LineTo(positions[positions.len() - 1]));
+        },
+
+        SmoothingMode::CubicBezier => {
+            instructions.push(Instruction::MoveTo(positions[0]));
+            for (control1, control2, position) in catmull_rom_to_bezier(&positions, sharpness) {
+                instructions.push(Instruction::CubicTo(control1, control2, position));
+            }
+        },
     }
 }
 
 pub fn smooth(positions : Vec<Vec2>, nb_points: usize, sharpness: f32) -> Vec<Vec2> {
-    let mut smoothed = Vec::new();
     if positions.len() < 2 {
         return positions;
     }
-    
-    smoothed.push(positions[0]);
-    
+
+    let mut smoothed = vec![positions[0]];
+
     for index in 1..(positions.len() - 1) {
         let p1 = positions[index - 1];
         let p2 = positions[index];
@@ -99,12 +264,187 @@ pub fn smooth(positions : Vec<Vec2>, nb_points: usize, sharpness: f32) -> Vec<Ve
             let s = pt as f32 / nb_points as f32;
             let l1 = p1 + dp12 * s;
             let l2 = p2 + dp23 * s;
-            
+
             smoothed.push(l1 + (l2 - l1) * s);
         }
     }
 
     smoothed.push(positions[positions.len() - 1]);
-    
-    return smoothed;
-}
\ No newline at end of file
+
+    smoothed
+}
+
+// Each iteration doubles the number of points, so a few are already plenty.
+const MAX_CHAIKIN_ITERATIONS : usize = 5;
+
+/// Iterations cutting each corner in at least `nb_points` segments, at most `MAX_CHAIKIN_ITERATIONS`.
+pub fn chaikin_iterations(nb_points: usize) -> usize {
+    (nb_points.max(2).next_power_of_two().trailing_zeros() as usize).min(MAX_CHAIKIN_ITERATIONS)
+}
+
+/// A sharpness of 0 cuts corners at the classic quarter ratio, 1 leaves the polyline untouched.
+///
+/// Iterations are capped at `MAX_CHAIKIN_ITERATIONS`.
+pub fn chaikin(positions : Vec<Vec2>, iterations: usize, sharpness: f32) -> Vec<Vec2> {
+    let ratio = 0.25_f32 * (1_f32 - sharpness.clamp(0_f32, 1_f32));
+    let mut smoothed = positions;
+
+    for _ in 0..iterations.min(MAX_CHAIKIN_ITERATIONS) {
+        if smoothed.len() < 3 || ratio <= 0_f32 {
+            break;
+        }
+
+        let mut next = vec![smoothed[0]];
+        for index in 0..(smoothed.len() - 1) {
+            let p1 = smoothed[index];
+            let p2 = smoothed[index + 1];
+            if index > 0 {
+                next.push(p1.lerp(p2, ratio));
+            }
+
+            if index < smoothed.len() - 2 {
+                next.push(p1.lerp(p2, 1_f32 - ratio));
+            }
+        }
+
+        next.push(smoothed[smoothed.len() - 1]);
+        smoothed = next;
+    }
+
+    smoothed
+}
+
+// Tangents of a centripetal (alpha = 0.5) Catmull-Rom spline at every point, one-sided at both ends.
+fn catmull_rom_tangents(positions : &[Vec2]) -> Vec<Vec2> {
+    let knot = |a: Vec2, b: Vec2| a.distance(b).sqrt().max(f32::EPSILON);
+    let count = positions.len();
+    let mut tangents = Vec::with_capacity(count);
+
+    for index in 0..count {
+        if index == 0 {
+            tangents.push((positions[1] - positions[0]) / knot(positions[0], positions[1]));
+        }
+        else if index == count - 1 {
+            tangents.push((positions[index] - positions[index - 1]) / knot(positions[index - 1], positions[index]));
+        }
+        else {
+            let p0 = positions[index - 1];
+            let p1 = positions[index];
+            let p2 = positions[index + 1];
+            let t01 = knot(p0, p1);
+            let t12 = knot(p1, p2);
+
+            tangents.push((p1 - p0) / t01 - (p2 - p0) / (t01 + t12) + (p2 - p1) / t12);
+        }
+    }
+
+    tangents
+}
+
+pub fn catmull_rom(positions : Vec<Vec2>, nb_points: usize) -> Vec<Vec2> {
+    if positions.len() < 3 || nb_points < 2 {
+        return positions;
+    }
+
+    let mut smoothed = vec![positions[0]];
+    for (control1, control2, position) in catmull_rom_to_bezier(&positions, 0_f32) {
+        let start = smoothed[smoothed.len() - 1];
+        for pt in 1..(nb_points + 1) {
+            let s = pt as f32 / nb_points as f32;
+            smoothed.push(cubic_point(start, control1, control2, position, s));
+        }
+    }
+
+    smoothed
+}
+
+/// Returns (control1, control2, end) for each span, the start being the end of the previous span.
+/// The sharpness shortens the tangents, 1 giving back the original polyline.
+pub fn catmull_rom_to_bezier(positions : &[Vec2], sharpness: f32) -> Vec<(Vec2, Vec2, Vec2)> {
+    let mut curves = Vec::new();
+    if positions.len() < 2 {
+        return curves;
+    }
+
+    let tension = 1_f32 - sharpness.clamp(0_f32, 1_f32);
+    let tangents = catmull_rom_tangents(positions);
+    for index in 0..(positions.len() - 1) {
+        let p1 = positions[index];
+        let p2 = positions[index + 1];
+        let span = p1.distance(p2).sqrt();
+
+        curves.push((
+            p1 + tangents[index] * span * tension / 3_f32,
+            p2 - tangents[index + 1] * span * tension / 3_f32,
+            p2,
+        ));
+    }
+
+    curves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline() -> Vec<Vec2> {
+        vec![Vec2::new(0_f32, 0_f32), Vec2::new(10_f32, 0_f32), Vec2::new(10_f32, 10_f32), Vec2::new(25_f32, 5_f32), Vec2::new(30_f32, 20_f32)]
+    }
+
+    #[test]
+    fn chaikin_with_full_sharpness_keeps_the_polyline() {
+        assert_eq!(chaikin(polyline(), 3, 1_f32), polyline());
+    }
+
+    #[test]
+    fn smoothing_keeps_the_endpoints() {
+        let positions = polyline();
+        let (first, last) = (positions[0], positions[positions.len() - 1]);
+        for smoothed in [chaikin(positions.clone(), 3, 0.5_f32), smooth(positions.clone(), 4, 0.5_f32), catmull_rom(positions.clone(), 4)] {
+            assert_eq!(smoothed.first(), Some(&first));
+            assert_eq!(smoothed.last(), Some(&last));
+        }
+
+        for mode in [SmoothingMode::Quadratic, SmoothingMode::Chaikin, SmoothingMode::CatmullRom, SmoothingMode::QuadraticCurve, SmoothingMode::CubicBezier] {
+            let mut instructions = Vec::new();
+            smooth_into_instructions(positions.clone(), mode, 4, 0.5_f32, &mut instructions);
+            assert!(matches!(instructions.first(), Some(Instruction::MoveTo(position)) if *position == first), "{:?}", mode);
+            assert_eq!(instructions.last().map(Instruction::end_point), Some(last), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn chaikin_iterations_are_capped() {
+        assert_eq!(chaikin_iterations(1), 1);
+        assert_eq!(chaikin_iterations(4), 2);
+        assert_eq!(chaikin_iterations(5), 3);
+        assert_eq!(chaikin_iterations(100_000), MAX_CHAIKIN_ITERATIONS);
+
+        // Every iteration turns n points into 2n - 2.
+        let capped = chaikin(polyline(), 100, 0_f32);
+        let mut count = polyline().len();
+        for _ in 0..MAX_CHAIKIN_ITERATIONS {
+            count = 2 * count - 2;
+        }
+
+        assert_eq!(capped.len(), count);
+        assert_eq!(capped, chaikin(polyline(), MAX_CHAIKIN_ITERATIONS, 0_f32));
+    }
+
+    #[test]
+    fn catmull_rom_goes_through_its_control_points() {
+        let positions = polyline();
+        let nb_points = 6;
+        let smoothed = catmull_rom(positions.clone(), nb_points);
+        assert_eq!(smoothed.len(), (positions.len() - 1) * nb_points + 1);
+        for (index, position) in positions.iter().enumerate() {
+            assert!(smoothed[index * nb_points].distance(*position) < 1e-4_f32, "{} misses {}", index, position);
+        }
+
+        let curves = catmull_rom_to_bezier(&positions, 0.3_f32);
+        assert_eq!(curves.len(), positions.len() - 1);
+        for (curve, position) in curves.iter().zip(positions.iter().skip(1)) {
+            assert_eq!(curve.2, *position);
+        }
+    }
+}